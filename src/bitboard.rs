@@ -0,0 +1,261 @@
+use crate::{
+    chessman::{Chessman, PieceKind},
+    square::Square,
+    Color,
+};
+
+/// One bit per square, bit `Square::index`. The upper 38 bits are unused.
+pub type Bitboard = u128;
+
+// the tables below are filled with lines counted from the top like
+// `Square::index`, not with ranks
+const WIDTH: i32 = 9;
+const HEIGHT: i32 = 10;
+
+pub fn bit(square: Square) -> Bitboard {
+    1 << square.index()
+}
+
+/// Iterate the squares of the set bits, from the top left.
+pub fn squares(mut bits: Bitboard) -> impl Iterator<Item = Square> {
+    std::iter::from_fn(move || {
+        if bits == 0 {
+            return None;
+        }
+        let index = bits.trailing_zeros() as usize;
+        bits &= bits - 1;
+        Square::from_index(index)
+    })
+}
+
+const fn on_board(col: i32, line: i32) -> bool {
+    col >= 0 && col < WIDTH && line >= 0 && line < HEIGHT
+}
+
+const fn at(col: i32, line: i32) -> Bitboard {
+    1 << (line * WIDTH + col)
+}
+
+// red owns the bottom half of the board, so index 0 of the colored tables is red
+const fn in_palace(col: i32, line: i32, red: bool) -> bool {
+    col >= 3
+        && col <= 5
+        && if red {
+            line >= 7 && line < HEIGHT
+        } else {
+            line >= 0 && line <= 2
+        }
+}
+
+const fn own_half(line: i32, red: bool) -> bool {
+    if red {
+        line >= 5
+    } else {
+        line <= 4
+    }
+}
+
+const ORTHOGONAL: [(i32, i32); 4] = [(0, 1), (0, -1), (1, 0), (-1, 0)];
+const DIAGONAL: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+const HORSE_OFFSETS: [(i32, i32); 8] = [
+    (1, 2),
+    (1, -2),
+    (-1, 2),
+    (-1, -2),
+    (2, 1),
+    (2, -1),
+    (-2, 1),
+    (-2, -1),
+];
+
+// one step inside the own palace, `offsets` tells kings from advisors
+const fn palace_table(offsets: [(i32, i32); 4]) -> [[Bitboard; 90]; 2] {
+    let mut table = [[0; 90]; 2];
+    let mut color = 0;
+    while color < 2 {
+        let mut sq = 0;
+        while sq < 90 {
+            let (col, line) = (sq % WIDTH, sq / WIDTH);
+            let mut i = 0;
+            while i < 4 {
+                let (c, l) = (col + offsets[i].0, line + offsets[i].1);
+                if in_palace(col, line, color == 0) && in_palace(c, l, color == 0) {
+                    table[color][sq as usize] |= at(c, l);
+                }
+                i += 1;
+            }
+            sq += 1;
+        }
+        color += 1;
+    }
+    table
+}
+
+static KING: [[Bitboard; 90]; 2] = palace_table(ORTHOGONAL);
+static ADVISOR: [[Bitboard; 90]; 2] = palace_table(DIAGONAL);
+
+// pawns step forward, and sideways once they crossed the river
+static PAWN: [[Bitboard; 90]; 2] = {
+    let mut table = [[0; 90]; 2];
+    let mut color = 0;
+    while color < 2 {
+        let red = color == 0;
+        let forward = if red { -1 } else { 1 };
+        let mut sq = 0;
+        while sq < 90 {
+            let (col, line) = (sq % WIDTH, sq / WIDTH);
+            if on_board(col, line + forward) {
+                table[color][sq as usize] |= at(col, line + forward);
+            }
+            if !own_half(line, red) {
+                if col > 0 {
+                    table[color][sq as usize] |= at(col - 1, line);
+                }
+                if col < WIDTH - 1 {
+                    table[color][sq as usize] |= at(col + 1, line);
+                }
+            }
+            sq += 1;
+        }
+        color += 1;
+    }
+    table
+};
+
+/// Target and eye of every elephant move, an empty target where the move
+/// would leave the board or cross the river.
+static ELEPHANT: [[[(Bitboard, Bitboard); 4]; 90]; 2] = {
+    let mut table = [[[(0, 0); 4]; 90]; 2];
+    let mut color = 0;
+    while color < 2 {
+        let mut sq = 0;
+        while sq < 90 {
+            let (col, line) = (sq % WIDTH, sq / WIDTH);
+            let mut i = 0;
+            while i < 4 {
+                let (dc, dl) = DIAGONAL[i];
+                let (c, l) = (col + 2 * dc, line + 2 * dl);
+                if on_board(c, l) && own_half(l, color == 0) {
+                    table[color][sq as usize][i] = (at(c, l), at(col + dc, line + dl));
+                }
+                i += 1;
+            }
+            sq += 1;
+        }
+        color += 1;
+    }
+    table
+};
+
+/// Target and leg of every horse move, the leg is the square next to the
+/// horse in the direction of the long step.
+static HORSE: [[(Bitboard, Bitboard); 8]; 90] = {
+    let mut table = [[(0, 0); 8]; 90];
+    let mut sq = 0;
+    while sq < 90 {
+        let (col, line) = (sq % WIDTH, sq / WIDTH);
+        let mut i = 0;
+        while i < 8 {
+            let (dc, dl) = HORSE_OFFSETS[i];
+            let (c, l) = (col + dc, line + dl);
+            if on_board(c, l) {
+                let leg = if dl == 2 || dl == -2 {
+                    at(col, line + dl / 2)
+                } else {
+                    at(col + dc / 2, line)
+                };
+                table[sq as usize][i] = (at(c, l), leg);
+            }
+            i += 1;
+        }
+        sq += 1;
+    }
+    table
+};
+
+// rooks stop on the first chessman, cannons move up to the screen and
+// capture the first chessman behind it
+fn slide(from: Square, occupied: Bitboard, cannon: bool) -> Bitboard {
+    let mut res = 0;
+    let (col, line) = (from.index() as i32 % WIDTH, from.index() as i32 / WIDTH);
+    for (dc, dl) in ORTHOGONAL {
+        let (mut col, mut line) = (col + dc, line + dl);
+        let mut screened = false;
+        while on_board(col, line) {
+            let b = at(col, line);
+            let blocked = occupied & b != 0;
+            if !cannon || !screened && !blocked || screened && blocked {
+                res |= b;
+            }
+            if blocked {
+                if !cannon || screened {
+                    break;
+                }
+                screened = true;
+            }
+            col += dc;
+            line += dl;
+        }
+    }
+    res
+}
+
+/// Squares the chessman on `from` can move to or capture on with the given
+/// occupancy, including squares held by its own side. Generals facing each
+/// other are left to the caller.
+pub fn attacks(chessman: &Chessman, from: Square, occupied: Bitboard) -> Bitboard {
+    let color = chessman.color() as usize;
+    let sq = from.index();
+    match chessman.kind() {
+        PieceKind::Rook => slide(from, occupied, false),
+        PieceKind::Cannon => slide(from, occupied, true),
+        PieceKind::Horse => HORSE[sq]
+            .iter()
+            .filter(|(_, leg)| occupied & leg == 0)
+            .fold(0, |res, (target, _)| res | target),
+        PieceKind::Elephant => ELEPHANT[color][sq]
+            .iter()
+            .filter(|(_, eye)| occupied & eye == 0)
+            .fold(0, |res, (target, _)| res | target),
+        PieceKind::Advisor => ADVISOR[color][sq],
+        PieceKind::King => KING[color][sq],
+        PieceKind::Pawn => PAWN[color][sq],
+    }
+}
+
+/// Where every kind of chessman stands, kept next to the square array of
+/// `Layout`.
+#[derive(Clone, PartialEq)]
+pub struct Bitboards {
+    pieces: [Bitboard; Chessman::COUNT],
+    sides: [Bitboard; 2],
+}
+
+impl Bitboards {
+    pub fn new() -> Self {
+        Self {
+            pieces: [0; Chessman::COUNT],
+            sides: [0; 2],
+        }
+    }
+
+    /// Flip the bit of `chessman` on `square`, putting it there or
+    /// taking it away.
+    pub fn toggle(&mut self, chessman: &Chessman, square: Square) {
+        let b = bit(square);
+        self.pieces[chessman.index()] ^= b;
+        self.sides[chessman.color() as usize] ^= b;
+    }
+
+    pub fn pieces(&self, chessman: &Chessman) -> Bitboard {
+        self.pieces[chessman.index()]
+    }
+
+    pub fn side(&self, side: Color) -> Bitboard {
+        self.sides[side as usize]
+    }
+
+    pub fn occupied(&self) -> Bitboard {
+        self.sides[0] | self.sides[1]
+    }
+}
@@ -1,22 +1,40 @@
-use crate::input::Move;
+use crate::{error::ChessError, input::Move, square::Square};
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
-pub enum Chessman {
-    KingRed,
-    AdvisorRed,
-    ElephantRed,
-    HorseRed,
-    RookRed,
-    CannonRed,
-    PawnRed,
-    KingBlack,
-    AdvisorBlack,
-    ElephantBlack,
-    HorseBlack,
-    RookBlack,
-    CannonBlack,
-    PawnBlack,
-    BOARD,
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Color {
+    Red,
+    Black,
+}
+
+impl Color {
+    pub fn opponent(&self) -> Color {
+        match self {
+            Color::Red => Color::Black,
+            Color::Black => Color::Red,
+        }
+    }
+    pub fn owns(&self, chessman: &Chessman) -> bool {
+        chessman.color() == *self
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum PieceKind {
+    King,
+    Advisor,
+    Elephant,
+    Horse,
+    Rook,
+    Cannon,
+    Pawn,
+}
+
+/// A chessman of one side. Kind and side are kept apart so neither can be
+/// mistaken for the other.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Chessman {
+    kind: PieceKind,
+    color: Color,
 }
 
 #[derive(PartialEq, Clone, Copy)]
@@ -24,252 +42,250 @@ pub enum ChessDisplayMode {
     Unicode,
     Character,
 }
+
+impl PieceKind {
+    pub const ALL: [PieceKind; 7] = [
+        PieceKind::King,
+        PieceKind::Advisor,
+        PieceKind::Elephant,
+        PieceKind::Horse,
+        PieceKind::Rook,
+        PieceKind::Cannon,
+        PieceKind::Pawn,
+    ];
+}
+
 impl Chessman {
-    pub fn to_string(&self, mode: ChessDisplayMode) -> String {
-        let c = match mode {
-            ChessDisplayMode::Unicode => match self {
-                Chessman::KingBlack => "🩧",
-                Chessman::AdvisorBlack => "🩨",
-                Chessman::ElephantBlack => "🩩",
-                Chessman::HorseBlack => "🩪",
-                Chessman::RookBlack => "🩫",
-                Chessman::CannonBlack => "🩬",
-                Chessman::PawnBlack => "🩭",
-                Chessman::KingRed => "🩠",
-                Chessman::AdvisorRed => "🩡",
-                Chessman::ElephantRed => "🩢",
-                Chessman::HorseRed => "🩣",
-                Chessman::RookRed => "🩤",
-                Chessman::CannonRed => "🩥",
-                Chessman::PawnRed => "🩦",
-                Chessman::BOARD => "",
+    /// Number of distinct chessmen, the range of `index`.
+    pub(crate) const COUNT: usize = 14;
+
+    pub fn new(kind: PieceKind, color: Color) -> Self {
+        Self { kind, color }
+    }
+
+    pub fn kind(&self) -> PieceKind {
+        self.kind
+    }
+
+    pub fn color(&self) -> Color {
+        self.color
+    }
+
+    /// Red chessmen first, each side in the order of `PieceKind`.
+    pub(crate) fn index(&self) -> usize {
+        self.color as usize * PieceKind::ALL.len() + self.kind as usize
+    }
+
+    pub(crate) fn to_string(self, mode: ChessDisplayMode) -> String {
+        let c = match (mode, self.color) {
+            (ChessDisplayMode::Unicode, Color::Black) => match self.kind {
+                PieceKind::King => "🩧",
+                PieceKind::Advisor => "🩨",
+                PieceKind::Elephant => "🩩",
+                PieceKind::Horse => "🩪",
+                PieceKind::Rook => "🩫",
+                PieceKind::Cannon => "🩬",
+                PieceKind::Pawn => "🩭",
+            },
+            (ChessDisplayMode::Unicode, Color::Red) => match self.kind {
+                PieceKind::King => "🩠",
+                PieceKind::Advisor => "🩡",
+                PieceKind::Elephant => "🩢",
+                PieceKind::Horse => "🩣",
+                PieceKind::Rook => "🩤",
+                PieceKind::Cannon => "🩥",
+                PieceKind::Pawn => "🩦",
             },
-            ChessDisplayMode::Character => match self {
-                Chessman::KingBlack => "将",
-                Chessman::AdvisorBlack => "士",
-                Chessman::ElephantBlack => "象",
-                Chessman::HorseBlack => "马",
-                Chessman::RookBlack => "车",
-                Chessman::CannonBlack => "炮",
-                Chessman::PawnBlack => "卒",
-                Chessman::KingRed => "\x1b[31;1m帅\x1b[0m",
-                Chessman::AdvisorRed => "\x1b[31;1m仕\x1b[0m",
-                Chessman::ElephantRed => "\x1b[31;1m相\x1b[0m",
-                Chessman::HorseRed => "\x1b[31;1m马\x1b[0m",
-                Chessman::RookRed => "\x1b[31;1m车\x1b[0m",
-                Chessman::CannonRed => "\x1b[31;1m炮\x1b[0m",
-                Chessman::PawnRed => "\x1b[31;1m兵\x1b[0m",
-                Chessman::BOARD => "",
+            (ChessDisplayMode::Character, Color::Black) => match self.kind {
+                PieceKind::King => "将",
+                PieceKind::Advisor => "士",
+                PieceKind::Elephant => "象",
+                PieceKind::Horse => "马",
+                PieceKind::Rook => "车",
+                PieceKind::Cannon => "炮",
+                PieceKind::Pawn => "卒",
+            },
+            (ChessDisplayMode::Character, Color::Red) => match self.kind {
+                PieceKind::King => "\x1b[31;1m帅\x1b[0m",
+                PieceKind::Advisor => "\x1b[31;1m仕\x1b[0m",
+                PieceKind::Elephant => "\x1b[31;1m相\x1b[0m",
+                PieceKind::Horse => "\x1b[31;1m马\x1b[0m",
+                PieceKind::Rook => "\x1b[31;1m车\x1b[0m",
+                PieceKind::Cannon => "\x1b[31;1m炮\x1b[0m",
+                PieceKind::Pawn => "\x1b[31;1m兵\x1b[0m",
             },
         };
         c.to_string()
     }
 
-    pub fn to_fen_char(&self) -> char {
-        match self {
-            Chessman::KingBlack => 'k',
-            Chessman::AdvisorBlack => 'a',
-            Chessman::ElephantBlack => 'b',
-            Chessman::HorseBlack => 'n',
-            Chessman::RookBlack => 'r',
-            Chessman::CannonBlack => 'c',
-            Chessman::PawnBlack => 'p',
-            Chessman::KingRed => 'K',
-            Chessman::AdvisorRed => 'A',
-            Chessman::ElephantRed => 'B',
-            Chessman::HorseRed => 'N',
-            Chessman::RookRed => 'R',
-            Chessman::CannonRed => 'C',
-            Chessman::PawnRed => 'P',
-            Chessman::BOARD => ' ',
+    /// The character used in traditional chinese notation.
+    pub(crate) fn to_chinese_char(self) -> char {
+        match (self.kind, self.color) {
+            (PieceKind::King, Color::Black) => '将',
+            (PieceKind::Advisor, Color::Black) => '士',
+            (PieceKind::Elephant, Color::Black) => '象',
+            (PieceKind::Pawn, Color::Black) => '卒',
+            (PieceKind::King, Color::Red) => '帅',
+            (PieceKind::Advisor, Color::Red) => '仕',
+            (PieceKind::Elephant, Color::Red) => '相',
+            (PieceKind::Pawn, Color::Red) => '兵',
+            (PieceKind::Horse, _) => '马',
+            (PieceKind::Rook, _) => '车',
+            (PieceKind::Cannon, _) => '炮',
         }
     }
 
-    pub fn into_red(&mut self) {
-        *self = match self.clone() {
-            Chessman::KingBlack => Chessman::KingRed,
-            Chessman::AdvisorBlack => Chessman::AdvisorRed,
-            Chessman::ElephantBlack => Chessman::ElephantRed,
-            Chessman::HorseBlack => Chessman::HorseRed,
-            Chessman::RookBlack => Chessman::RookRed,
-            Chessman::CannonBlack => Chessman::CannonRed,
-            Chessman::PawnBlack => Chessman::PawnRed,
-            v @ _ => v,
-        }
-    }
-    pub fn into_black(&mut self) {
-        *self = match self.clone() {
-            Chessman::KingRed => Chessman::KingBlack,
-            Chessman::AdvisorRed => Chessman::AdvisorBlack,
-            Chessman::ElephantRed => Chessman::ElephantBlack,
-            Chessman::HorseRed => Chessman::HorseBlack,
-            Chessman::RookRed => Chessman::RookBlack,
-            Chessman::CannonRed => Chessman::CannonBlack,
-            Chessman::PawnRed => Chessman::PawnBlack,
-            v @ _ => v,
+    /// The letter used in WXF notation, the same for both sides.
+    pub(crate) fn to_wxf_char(self) -> char {
+        match self.kind {
+            PieceKind::King => 'K',
+            PieceKind::Advisor => 'A',
+            PieceKind::Elephant => 'E',
+            PieceKind::Horse => 'H',
+            PieceKind::Rook => 'R',
+            PieceKind::Cannon => 'C',
+            PieceKind::Pawn => 'P',
         }
     }
-    pub fn to_red(&self) -> Self {
-        match self {
-            Chessman::KingBlack => Chessman::KingRed,
-            Chessman::AdvisorBlack => Chessman::AdvisorRed,
-            Chessman::ElephantBlack => Chessman::ElephantRed,
-            Chessman::HorseBlack => Chessman::HorseRed,
-            Chessman::RookBlack => Chessman::RookRed,
-            Chessman::CannonBlack => Chessman::CannonRed,
-            Chessman::PawnBlack => Chessman::PawnRed,
-            v @ _ => *v,
-        }
-    }
-    pub fn to_black(&self) -> Self {
-        match self {
-            Chessman::KingRed => Chessman::KingBlack,
-            Chessman::AdvisorRed => Chessman::AdvisorBlack,
-            Chessman::ElephantRed => Chessman::ElephantBlack,
-            Chessman::HorseRed => Chessman::HorseBlack,
-            Chessman::RookRed => Chessman::RookBlack,
-            Chessman::CannonRed => Chessman::CannonBlack,
-            Chessman::PawnRed => Chessman::PawnBlack,
-            v @ _ => *v,
+
+    pub fn to_fen_char(&self) -> char {
+        let c = match self.kind {
+            PieceKind::King => 'K',
+            PieceKind::Advisor => 'A',
+            PieceKind::Elephant => 'B',
+            PieceKind::Horse => 'N',
+            PieceKind::Rook => 'R',
+            PieceKind::Cannon => 'C',
+            PieceKind::Pawn => 'P',
+        };
+        match self.color {
+            Color::Red => c,
+            Color::Black => c.to_ascii_lowercase(),
         }
     }
-    pub fn change_color(&self) -> Self {
-        if self.is_black() {
-            self.to_red()
+
+    pub fn from_fen_char(c: char) -> Option<Self> {
+        let kind = match c.to_ascii_uppercase() {
+            'K' => PieceKind::King,
+            'A' => PieceKind::Advisor,
+            'B' | 'E' => PieceKind::Elephant,
+            'N' | 'H' => PieceKind::Horse,
+            'R' => PieceKind::Rook,
+            'C' => PieceKind::Cannon,
+            'P' => PieceKind::Pawn,
+            _ => return None,
+        };
+        let color = if c.is_ascii_uppercase() {
+            Color::Red
         } else {
-            self.to_black()
-        }
+            Color::Black
+        };
+        Some(Self::new(kind, color))
     }
-    pub fn is_black(&self) -> bool {
-        match self {
-            Chessman::KingRed => false,
-            Chessman::AdvisorRed => false,
-            Chessman::ElephantRed => false,
-            Chessman::HorseRed => false,
-            Chessman::RookRed => false,
-            Chessman::CannonRed => false,
-            Chessman::PawnRed => false,
-            _ => true,
+
+    /// How many of this chessman one side owns at the start of a game.
+    pub(crate) fn max_count(&self) -> usize {
+        match self.kind {
+            PieceKind::King => 1,
+            PieceKind::Pawn => 5,
+            _ => 2,
         }
     }
-    pub fn is_red(&self) -> bool {
-        !self.is_black()
-    }
-    pub fn is_move_straight(&self) -> bool {
+
+    pub(crate) fn is_move_straight(&self) -> bool {
         self.is_king() || self.is_rook() || self.is_cannon() || self.is_pawn()
     }
-    pub fn is_king(&self) -> bool {
-        *self == Chessman::KingBlack || *self == Chessman::KingRed
+    pub(crate) fn is_king(&self) -> bool {
+        self.kind == PieceKind::King
     }
-    pub fn is_rook(&self) -> bool {
-        *self == Chessman::RookBlack || *self == Chessman::RookRed
+    pub(crate) fn is_rook(&self) -> bool {
+        self.kind == PieceKind::Rook
     }
-    pub fn is_cannon(&self) -> bool {
-        *self == Chessman::CannonBlack || *self == Chessman::CannonRed
+    pub(crate) fn is_cannon(&self) -> bool {
+        self.kind == PieceKind::Cannon
     }
-    pub fn is_pawn(&self) -> bool {
-        *self == Chessman::PawnBlack || *self == Chessman::PawnRed
+    pub(crate) fn is_pawn(&self) -> bool {
+        self.kind == PieceKind::Pawn
     }
-    pub fn is_elephant(&self) -> bool {
-        *self == Chessman::ElephantBlack || *self == Chessman::ElephantRed
+    pub(crate) fn is_elephant(&self) -> bool {
+        self.kind == PieceKind::Elephant
     }
-    pub fn is_horse(&self) -> bool {
-        *self == Chessman::HorseBlack || *self == Chessman::HorseRed
+    pub(crate) fn is_horse(&self) -> bool {
+        self.kind == PieceKind::Horse
     }
-    pub fn is_advisor(&self) -> bool {
-        *self == Chessman::AdvisorBlack || *self == Chessman::AdvisorRed
+    pub(crate) fn is_advisor(&self) -> bool {
+        self.kind == PieceKind::Advisor
     }
-    pub fn is_different_color(&self, other: &Chessman) -> bool {
-        self.is_red() && other.is_black() || self.is_black() && other.is_red()
+    pub(crate) fn is_cross_river(&self, square: &Square) -> bool {
+        !square.is_own_half(self.color())
     }
-    pub fn is_cross_river(&self, coordinate: &(usize, usize)) -> bool {
-        if self.is_red() {
-            coordinate.1 < 5
-        } else {
-            coordinate.1 >= 5
-        }
+    pub(crate) fn is_move_forward(&self, m: &Move) -> bool {
+        m.to.rank().relative(self.color()) > m.from.rank().relative(self.color())
     }
-    pub fn is_move_forward(&self, m: &Move) -> bool {
-        if self.is_red() {
-            m.to.1 < m.from.1
-        } else {
-            m.to.1 > m.from.1
-        }
-    }
-    pub fn is_move_backward(&self, m: &Move) -> bool {
-        if self.is_red() {
-            m.to.1 > m.from.1
-        } else {
-            m.to.1 < m.from.1
-        }
+    pub(crate) fn is_move_backward(&self, m: &Move) -> bool {
+        m.to.rank().relative(self.color()) < m.from.rank().relative(self.color())
     }
-    pub fn is_move_right(&self, m: &Move) -> bool {
-        if self.is_red() {
-            m.to.0 > m.from.0
-        } else {
-            m.to.0 < m.from.0
-        }
+    pub(crate) fn is_move_right(&self, m: &Move) -> bool {
+        m.to.file().relative(self.color()) > m.from.file().relative(self.color())
     }
-    pub fn is_move_left(&self, m: &Move) -> bool {
-        if self.is_red() {
-            m.to.0 < m.from.0
-        } else {
-            m.to.0 > m.from.0
-        }
+    pub(crate) fn is_move_left(&self, m: &Move) -> bool {
+        m.to.file().relative(self.color()) < m.from.file().relative(self.color())
     }
-    pub fn is_move_horizontally(&self, m: &Move) -> bool {
+    pub(crate) fn is_move_horizontally(&self, m: &Move) -> bool {
         self.is_move_left(m) || self.is_move_right(m)
     }
-    pub fn is_move_vertically(&self, m: &Move) -> bool {
-        self.is_move_backward(m) || self.is_move_forward(m)
+    pub(crate) fn is_move_one_step(&self, m: &Move) -> bool {
+        let files = m.from.file().index().abs_diff(m.to.file().index());
+        let ranks = m.from.rank().index().abs_diff(m.to.rank().index());
+        files + ranks == 1
     }
-    pub fn is_move_one_step(&self, m: &Move) -> bool {
-        let (from, to) = (m.from, m.to);
-        from.0.abs_diff(to.0) == 1 && from.1 == to.1 || from.1.abs_diff(to.1) == 1 && from.0 == to.0
+    pub(crate) fn is_in_palace(&self, square: &Square) -> bool {
+        square.is_in_palace(self.color())
     }
-    pub fn is_in_palace(&self, coordinate: &(usize, usize)) -> bool {
-        if self.is_black() {
-            coordinate.0 >= 3 && coordinate.0 <= 5 && coordinate.1 <= 2
+    /// Whether the chessman can ever reach `square` in a real game.
+    pub(crate) fn is_valid_position(&self, square: &Square) -> bool {
+        let col = square.file().index();
+        // count ranks from the owner's back rank
+        let rank = square.rank().relative(self.color());
+        if self.is_king() {
+            self.is_in_palace(square)
+        } else if self.is_advisor() {
+            self.is_in_palace(square) && (col + rank) % 2 == 1
+        } else if self.is_elephant() {
+            rank <= 4
+                && rank.is_multiple_of(2)
+                && col.is_multiple_of(2)
+                && (col / 2 + rank / 2) % 2 == 1
+        } else if self.is_pawn() {
+            if self.is_cross_river(square) {
+                true
+            } else {
+                (rank == 3 || rank == 4) && col.is_multiple_of(2)
+            }
         } else {
-            coordinate.0 >= 3 && coordinate.0 <= 5 && coordinate.1 >= 7 && coordinate.1 <= 9
+            true
         }
     }
 }
 
-impl From<char> for Chessman {
-    fn from(value: char) -> Self {
-        match value {
-            'k' => Chessman::KingBlack,
-            'K' => Chessman::KingRed,
-            '将' => Chessman::KingRed,
-            '帅' => Chessman::KingRed,
-            'a' => Chessman::AdvisorBlack,
-            'A' => Chessman::AdvisorRed,
-            '士' => Chessman::AdvisorRed,
-            '仕' => Chessman::AdvisorRed,
-            'e' => Chessman::ElephantBlack,
-            'E' => Chessman::ElephantRed,
-            'b' => Chessman::ElephantBlack,
-            'B' => Chessman::ElephantRed,
-            '相' => Chessman::ElephantRed,
-            '象' => Chessman::ElephantRed,
-            'n' => Chessman::HorseBlack,
-            'N' => Chessman::HorseRed,
-            'h' => Chessman::HorseBlack,
-            'H' => Chessman::HorseRed,
-            '马' => Chessman::HorseRed,
-            'r' => Chessman::RookBlack,
-            'R' => Chessman::RookRed,
-            '车' => Chessman::RookRed,
-            'c' => Chessman::CannonBlack,
-            'C' => Chessman::CannonRed,
-            '炮' => Chessman::CannonRed,
-            '砲' => Chessman::CannonRed,
-            'p' => Chessman::PawnBlack,
-            'P' => Chessman::PawnRed,
-            '卒' => Chessman::PawnRed,
-            '兵' => Chessman::PawnRed,
-            _ => panic!("unsupported chess character {value}"),
+impl TryFrom<char> for Chessman {
+    type Error = ChessError;
+
+    /// FEN letters carry their side, chinese characters are read as red.
+    fn try_from(value: char) -> Result<Self, ChessError> {
+        if let Some(chessman) = Chessman::from_fen_char(value) {
+            return Ok(chessman);
         }
+        let kind = match value {
+            '将' | '帅' => PieceKind::King,
+            '士' | '仕' => PieceKind::Advisor,
+            '相' | '象' => PieceKind::Elephant,
+            '马' => PieceKind::Horse,
+            '车' => PieceKind::Rook,
+            '炮' | '砲' => PieceKind::Cannon,
+            '卒' | '兵' => PieceKind::Pawn,
+            _ => return Err(ChessError::BadChessman(value)),
+        };
+        Ok(Chessman::new(kind, Color::Red))
     }
 }
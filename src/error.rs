@@ -0,0 +1,61 @@
+use core::fmt;
+
+use crate::{fen::FenError, input::Move, square::Square, Color};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ChessError {
+    /// A move starts on a square without a chessman.
+    EmptySquare(Square),
+    BadChessman(char),
+    /// Neither a digit from 1 to 9 nor a chinese number.
+    BadNumber(char),
+    /// Not one of 进, 退, 平 or their WXF signs.
+    BadDirection(char),
+    /// Chinese and WXF notation take exactly four characters.
+    BadLength(usize),
+    BadCoordinates(String),
+    /// The notation names a chessman the board doesn't have.
+    NoSuchChessman(String),
+    /// The move would leave the board.
+    TooFar,
+    IllegalMove(Move),
+    /// The chessman belongs to the other side, the color is the side to move.
+    WrongSide(Color),
+    /// `perft` and `divide` take a number of plies.
+    BadDepth(String),
+    /// Not one of `classic`, `iccs` or `wxf`.
+    BadInputMode(String),
+    Fen(FenError),
+}
+
+impl fmt::Display for ChessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChessError::EmptySquare(square) => {
+                write!(f, "cannot find chessman on position {square}")
+            }
+            ChessError::BadChessman(c) => write!(f, "unsupported chess character {c}"),
+            ChessError::BadNumber(c) => write!(f, "unsupported number {c}"),
+            ChessError::BadDirection(c) => write!(f, "unsupported movement {c}"),
+            ChessError::BadLength(len) => {
+                write!(f, "input len is not equal to 4, got {len}")
+            }
+            ChessError::BadCoordinates(s) => write!(f, "cannot parse coordinates {s}"),
+            ChessError::NoSuchChessman(s) => write!(f, "cannot find chessman {s}"),
+            ChessError::TooFar => write!(f, "invalid movement, move too far"),
+            ChessError::IllegalMove(m) => write!(f, "invalid movement {m}"),
+            ChessError::WrongSide(side) => write!(f, "it is {side:?}'s turn"),
+            ChessError::BadDepth(s) => write!(f, "invalid depth {s}"),
+            ChessError::BadInputMode(s) => write!(f, "unsupported input mode {s}"),
+            ChessError::Fen(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for ChessError {}
+
+impl From<FenError> for ChessError {
+    fn from(e: FenError) -> Self {
+        ChessError::Fen(e)
+    }
+}
@@ -0,0 +1,266 @@
+use crate::{
+    chessman::Chessman,
+    input::Move,
+    layout::Layout,
+    square::{Rank, Square},
+    Color,
+};
+
+// piece-square tables from red's point of view, line 0 is black's back rank,
+// black reads them rotated by 180 degrees
+type Table = [[i32; 9]; 10];
+
+#[rustfmt::skip]
+const KING: Table = [
+    [0; 9], [0; 9], [0; 9], [0; 9], [0; 9], [0; 9], [0; 9],
+    [0, 0, 0, -20, -25, -20, 0, 0, 0],
+    [0, 0, 0, -10, -10, -10, 0, 0, 0],
+    [0, 0, 0,   5,  10,   5, 0, 0, 0],
+];
+#[rustfmt::skip]
+const ADVISOR: Table = [
+    [0; 9], [0; 9], [0; 9], [0; 9], [0; 9], [0; 9], [0; 9],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 5, 0, 0, 0, 0],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0],
+];
+#[rustfmt::skip]
+const ELEPHANT: Table = [
+    [0; 9], [0; 9], [0; 9], [0; 9], [0; 9],
+    [ 0, 0, -5, 0,  0, 0, -5, 0,  0],
+    [ 0, 0,  0, 0,  0, 0,  0, 0,  0],
+    [-5, 0,  0, 0, 10, 0,  0, 0, -5],
+    [ 0, 0,  0, 0,  0, 0,  0, 0,  0],
+    [ 0, 0,  0, 0,  0, 0,  0, 0,  0],
+];
+#[rustfmt::skip]
+const HORSE: Table = [
+    [  0, -5,  5, 10,   5, 10,  5, -5,   0],
+    [  5, 15, 25, 20,  10, 20, 25, 15,   5],
+    [ 10, 20, 25, 30,  25, 30, 25, 20,  10],
+    [ 10, 25, 25, 30,  25, 30, 25, 25,  10],
+    [  5, 15, 20, 25,  25, 25, 20, 15,   5],
+    [  5, 10, 15, 20,  20, 20, 15, 10,   5],
+    [  0,  5, 10, 10,  15, 10, 10,  5,   0],
+    [  0,  5,  5, 10,   5, 10,  5,  5,   0],
+    [ -5,  0,  0,  5, -10,  5,  0,  0,  -5],
+    [-10, -5,  0, -5,   0, -5,  0, -5, -10],
+];
+#[rustfmt::skip]
+const ROOK: Table = [
+    [ 10, 15, 10, 20, 20, 20, 10, 15,  10],
+    [ 15, 20, 15, 25, 30, 25, 15, 20,  15],
+    [ 10, 15, 10, 20, 20, 20, 10, 15,  10],
+    [ 10, 20, 15, 20, 20, 20, 15, 20,  10],
+    [ 10, 15, 15, 20, 20, 20, 15, 15,  10],
+    [  5, 15, 10, 15, 15, 15, 10, 15,   5],
+    [  0, 10,  5, 15, 10, 15,  5, 10,   0],
+    [ -5,  5,  0, 10,  5, 10,  0,  5,  -5],
+    [  0,  5,  0, 10,  0, 10,  0,  5,   0],
+    [-10,  5,  0, 10,  0, 10,  0,  5, -10],
+];
+#[rustfmt::skip]
+const CANNON: Table = [
+    [ 5, 5,  0, -5,  -5, -5,  0, 5,  5],
+    [ 5, 5,  0, -5, -10, -5,  0, 5,  5],
+    [ 0, 0,  0,  0,  -5,  0,  0, 0,  0],
+    [ 0, 0,  0,  0,   0,  0,  0, 0,  0],
+    [ 0, 0,  0,  0,   5,  0,  0, 0,  0],
+    [-5, 0,  5,  0,   5,  0,  5, 0, -5],
+    [ 0, 0,  0,  0,   5,  0,  0, 0,  0],
+    [ 5, 0, 10,  5,  15,  5, 10, 0,  5],
+    [ 0, 5,  5,  5,   5,  5,  5, 5,  0],
+    [ 0, 0,  5, 10,  10, 10,  5, 0,  0],
+];
+#[rustfmt::skip]
+const PAWN: Table = [
+    [ 0,  0,  0,  0,  0,  0,  0,  0,  0],
+    [20, 30, 45, 55, 60, 55, 45, 30, 20],
+    [20, 30, 45, 50, 50, 50, 45, 30, 20],
+    [15, 25, 35, 40, 40, 40, 35, 25, 15],
+    [10, 15, 20, 25, 25, 25, 20, 15, 10],
+    [ 0,  0,  0,  0,  0,  0,  0,  0,  0],
+    [ 0,  0, -5,  0,  5,  0, -5,  0,  0],
+    [0; 9], [0; 9], [0; 9],
+];
+
+/// Weights of the evaluation terms, all in the same unit as `pawn`.
+#[derive(Clone, Copy, Debug)]
+pub struct EvalConfig {
+    pub rook: i32,
+    pub cannon: i32,
+    pub horse: i32,
+    pub advisor: i32,
+    pub elephant: i32,
+    pub pawn: i32,
+    /// Extra value of a pawn that has crossed the river and can move sideways.
+    pub crossed_pawn: i32,
+    /// Per square a horse can jump to.
+    pub horse_mobility: i32,
+    /// A cannon on the file or rank of the enemy king with one screen between.
+    pub cannon_screen: i32,
+    /// A cannon on the file of the enemy king with nothing between (空头炮).
+    pub cannon_empty_head: i32,
+    /// A rook on a file without any pawn.
+    pub rook_open_file: i32,
+    /// Per missing advisor, for every enemy rook, horse or cannon across the river.
+    pub missing_advisor: i32,
+    /// Per missing elephant, for every enemy rook, horse or cannon across the river.
+    pub missing_elephant: i32,
+}
+
+impl Default for EvalConfig {
+    fn default() -> Self {
+        Self {
+            rook: 600,
+            cannon: 285,
+            horse: 270,
+            advisor: 120,
+            elephant: 120,
+            pawn: 60,
+            crossed_pawn: 40,
+            horse_mobility: 8,
+            cannon_screen: 20,
+            cannon_empty_head: 60,
+            rook_open_file: 25,
+            missing_advisor: 12,
+            missing_elephant: 10,
+        }
+    }
+}
+
+impl EvalConfig {
+    pub fn material(&self, chessman: &Chessman) -> i32 {
+        if chessman.is_rook() {
+            self.rook
+        } else if chessman.is_cannon() {
+            self.cannon
+        } else if chessman.is_horse() {
+            self.horse
+        } else if chessman.is_advisor() {
+            self.advisor
+        } else if chessman.is_elephant() {
+            self.elephant
+        } else if chessman.is_pawn() {
+            self.pawn
+        } else {
+            0
+        }
+    }
+}
+
+/// Score of the layout from `side`'s point of view.
+pub fn evaluate(layout: &Layout, side: Color, config: &EvalConfig) -> i32 {
+    score_of(layout, side, config) - score_of(layout, side.opponent(), config)
+}
+
+fn score_of(layout: &Layout, side: Color, config: &EvalConfig) -> i32 {
+    let enemy_king = layout.find_king(side.opponent());
+    let mut score = 0;
+    let (mut advisors, mut elephants, mut attackers) = (0, 0, 0);
+    for square in Square::all() {
+        let Some(c) = layout.get(square) else {
+            continue;
+        };
+        if !side.owns(&c) {
+            if !c.is_king() && !c.is_pawn() && c.is_cross_river(&square) {
+                attackers += 1;
+            }
+            continue;
+        }
+        score += config.material(&c) + piece_square(&c, square);
+        if c.is_pawn() && c.is_cross_river(&square) {
+            score += config.crossed_pawn;
+        } else if c.is_horse() {
+            score += config.horse_mobility * horse_mobility(layout, square);
+        } else if c.is_cannon() {
+            if let Some(king) = enemy_king {
+                score += cannon_score(layout, square, king, config);
+            }
+        } else if c.is_rook() {
+            let has_pawn = Rank::all().any(|rank| {
+                layout
+                    .get(Square::new(square.file(), rank))
+                    .is_some_and(|c| c.is_pawn())
+            });
+            if !has_pawn {
+                score += config.rook_open_file;
+            }
+        } else if c.is_advisor() {
+            advisors += 1;
+        } else if c.is_elephant() {
+            elephants += 1;
+        }
+    }
+    score -= attackers * (config.missing_advisor * (2 - advisors).max(0));
+    score -= attackers * (config.missing_elephant * (2 - elephants).max(0));
+    score
+}
+
+fn piece_square(chessman: &Chessman, square: Square) -> i32 {
+    let table = if chessman.is_king() {
+        &KING
+    } else if chessman.is_advisor() {
+        &ADVISOR
+    } else if chessman.is_elephant() {
+        &ELEPHANT
+    } else if chessman.is_horse() {
+        &HORSE
+    } else if chessman.is_rook() {
+        &ROOK
+    } else if chessman.is_cannon() {
+        &CANNON
+    } else {
+        &PAWN
+    };
+    // tables follow `Square::index`, rotating the board reverses the index
+    let index = if chessman.color() == Color::Red {
+        square.index()
+    } else {
+        Square::COUNT - 1 - square.index()
+    };
+    table[index / 9][index % 9]
+}
+
+// squares the horse can jump to, with free legs and no own chessman on them
+fn horse_mobility(layout: &Layout, from: Square) -> i32 {
+    const JUMPS: [(isize, isize); 8] = [
+        (1, 2),
+        (1, -2),
+        (-1, 2),
+        (-1, -2),
+        (2, 1),
+        (2, -1),
+        (-2, 1),
+        (-2, -1),
+    ];
+    let horse = layout.get(from).unwrap();
+    let mut res = 0;
+    for (x, y) in JUMPS {
+        let Some(to) = from.offset(x, y) else {
+            continue;
+        };
+        // the leg is next to the horse in the direction of the long step
+        let leg = from.offset(x / 2, y / 2).unwrap();
+        if layout.get(leg).is_some() {
+            continue;
+        }
+        match layout.get(to) {
+            Some(c) if c.color() == horse.color() => {}
+            _ => res += 1,
+        }
+    }
+    res
+}
+
+fn cannon_score(layout: &Layout, from: Square, king: Square, config: &EvalConfig) -> i32 {
+    if from.file() != king.file() && from.rank() != king.rank() {
+        return 0;
+    }
+    let m = Move { from, to: king };
+    match layout.get_bypass_chessman_num(&m) {
+        0 if from.file() == king.file() => config.cannon_empty_head,
+        1 => config.cannon_screen,
+        _ => 0,
+    }
+}
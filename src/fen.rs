@@ -0,0 +1,77 @@
+use core::fmt;
+
+use crate::{
+    chessman::{Chessman, PieceKind},
+    layout::Layout,
+    square::Square,
+    Color,
+};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum FenError {
+    BadPiece(char),
+    WrongRankCount(usize),
+    RankTooLong(usize),
+    RankTooShort(usize),
+    MissingSide,
+    BadSide(String),
+    BadCounter(String),
+    MissingKing(Color),
+    TooManyPieces(Chessman),
+    IllegalSquare(Chessman, Square),
+    /// The side that just moved has left its king in check.
+    OpponentInCheck(Color),
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FenError::BadPiece(c) => write!(f, "unsupported chessman {c}"),
+            FenError::WrongRankCount(n) => write!(f, "expect 10 ranks, got {n}"),
+            FenError::RankTooLong(line) => write!(f, "rank {line} has more than 9 columns"),
+            FenError::RankTooShort(line) => write!(f, "rank {line} has less than 9 columns"),
+            FenError::MissingSide => write!(f, "missing side to move"),
+            FenError::BadSide(s) => write!(f, "unsupported side to move {s}"),
+            FenError::BadCounter(s) => write!(f, "invalid move counter {s}"),
+            FenError::MissingKing(turn) => write!(f, "{:?} has no king", turn),
+            FenError::TooManyPieces(c) => write!(f, "too many {:?}", c),
+            FenError::IllegalSquare(c, square) => {
+                write!(f, "{:?} cannot stand on {}", c, square)
+            }
+            FenError::OpponentInCheck(side) => {
+                write!(f, "{:?} is in check but not to move", side)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+/// Reject positions that cannot arise in a real game with `turn` to move.
+pub fn validate_layout(layout: &Layout, turn: Color) -> Result<(), FenError> {
+    for turn in [Color::Red, Color::Black] {
+        let king = Chessman::new(PieceKind::King, turn);
+        if layout.find_king(turn).is_none() {
+            return Err(FenError::MissingKing(turn));
+        }
+        if layout.find_chessman(&king).map_or(0, |v| v.len()) > 1 {
+            return Err(FenError::TooManyPieces(king));
+        }
+    }
+    for square in Square::all() {
+        if let Some(c) = layout.get(square) {
+            if !c.is_valid_position(&square) {
+                return Err(FenError::IllegalSquare(c, square));
+            }
+            let count = layout.find_chessman(&c).map_or(0, |v| v.len());
+            if count > c.max_count() {
+                return Err(FenError::TooManyPieces(c));
+            }
+        }
+    }
+    // this includes kings facing each other on an open file
+    if layout.is_in_check(turn.opponent()) {
+        return Err(FenError::OpponentInCheck(turn.opponent()));
+    }
+    Ok(())
+}
@@ -1,198 +1,344 @@
 use core::fmt;
-use std::io;
 
 use crate::{
-    chessman::{self, Chessman},
-    layout::{self, Layout},
-    Chess,
+    chessman::{Chessman, PieceKind},
+    error::ChessError,
+    layout::Layout,
+    square::{File, Square},
+    Color,
 };
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum InputMode {
     Classic,
+    /// Coordinates such as `h2e2` or `h2-e2`, as printed by `Move`'s `Display`.
+    Iccs,
+    /// WXF notation such as `C2=5`, `H8+7`, `+R-1` or `R1.2`.
+    Wxf,
 }
+
+impl InputMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "classic" => Some(InputMode::Classic),
+            "iccs" => Some(InputMode::Iccs),
+            "wxf" => Some(InputMode::Wxf),
+            _ => None,
+        }
+    }
+}
+
 pub struct Input {
     mode: InputMode,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Move {
-    pub from: (usize, usize),
-    pub to: (usize, usize),
+    pub from: Square,
+    pub to: Square,
+}
+
+/// What the player asked for in the interactive loop.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Command {
+    Move(Move),
+    Undo,
+    Redo,
+    /// Let the computer play for the side to move.
+    Go,
+    /// Load a position, `fen <FEN>`.
+    Fen(String),
+    /// Count the leaf nodes `perft <depth>` plies deep.
+    Perft(usize),
+    /// `perft` split by the first move, `divide <depth>`.
+    Divide(usize),
+    /// Read moves in another notation from now on, `mode <name>`.
+    Mode(InputMode),
+    /// Hand stdin and stdout over to a GUI speaking UCCI.
+    Ucci,
+    /// Hand stdin and stdout over to a GUI speaking UCI with xiangqi FEN.
+    Uci,
+    /// Leave the game, `quit` or `exit`.
+    Quit,
 }
 
 impl fmt::Display for Move {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let from_col = ('a' as u8 + self.from.0 as u8) as char;
-        let from_line = 9 - self.from.1;
-        let to_col = ('a' as u8 + self.to.0 as u8) as char;
-        let to_line = 9 - self.to.1;
-        write!(f, "{from_col}{from_line}{to_col}{to_line}")
+        write!(f, "{}{}", self.from, self.to)
+    }
+}
+
+impl Move {
+    /// Parse ICCS coordinates such as `h2e2` or `h2-e2`.
+    pub fn from_iccs(s: &str) -> Option<Move> {
+        let s = s.trim();
+        if !s.is_ascii() {
+            return None;
+        }
+        let (from, to) = match s.len() {
+            4 => (&s[..2], &s[2..]),
+            5 if &s[2..3] == "-" => (&s[..2], &s[3..]),
+            _ => return None,
+        };
+        Some(Move {
+            from: Square::from_iccs(from)?,
+            to: Square::from_iccs(to)?,
+        })
+    }
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Input {
-    const C_NUMBER: [char; 9] = ['一', '二', '三', '四', '五', '六', '七', '八', '九'];
+    pub const C_NUMBER: [char; 9] = ['一', '二', '三', '四', '五', '六', '七', '八', '九'];
+    const C_CHESSMAN: [char; 12] = [
+        '帅', '将', '仕', '士', '相', '象', '马', '车', '炮', '砲', '兵', '卒',
+    ];
+    const WXF_CHESSMAN: [char; 9] = ['K', 'A', 'E', 'B', 'H', 'N', 'R', 'C', 'P'];
     pub fn new() -> Self {
         Input {
             mode: InputMode::Classic,
         }
     }
 
-    pub fn get_move(&self, layout: &Layout) -> Move {
-        loop {
-            let mut buffer = String::new();
-            io::stdin().read_line(&mut buffer).unwrap();
-            if let Some(m) = self.parse_input(&buffer, layout) {
-                return m;
+    pub fn set_mode(&mut self, mode: InputMode) {
+        self.mode = mode;
+    }
+
+    /// Parse one line of the interactive loop, a move in the current mode
+    /// or one of the commands.
+    pub fn parse_command(
+        &self,
+        line: &str,
+        layout: &Layout,
+        side: Color,
+    ) -> Result<Command, ChessError> {
+        let line = line.trim();
+        match line {
+            "quit" | "exit" => return Ok(Command::Quit),
+            "悔棋" | "undo" => return Ok(Command::Undo),
+            "还原" | "redo" => return Ok(Command::Redo),
+            "go" | "电脑" => return Ok(Command::Go),
+            "ucci" => return Ok(Command::Ucci),
+            "uci" => return Ok(Command::Uci),
+            _ => {}
+        }
+        if let Some(fen) = line.strip_prefix("fen ") {
+            return Ok(Command::Fen(fen.trim().to_string()));
+        }
+        for (prefix, command) in [
+            ("perft ", Command::Perft as fn(usize) -> Command),
+            ("divide ", Command::Divide),
+        ] {
+            if let Some(depth) = line.strip_prefix(prefix) {
+                return depth
+                    .trim()
+                    .parse()
+                    .map(command)
+                    .map_err(|_| ChessError::BadDepth(depth.trim().to_string()));
             }
-            // wrong input, wait another input
         }
+        if let Some(name) = line.strip_prefix("mode ") {
+            return InputMode::from_name(name.trim())
+                .map(Command::Mode)
+                .ok_or_else(|| ChessError::BadInputMode(name.trim().to_string()));
+        }
+        self.parse_input(line, layout, side).map(Command::Move)
     }
 
-    fn find_chessman(chars: &[char], layout: &Layout) -> Option<(Chessman, (usize, usize))> {
-        assert!(chars.len() == 2);
-        if chars[0] == '前' || chars[0] == '后' || chars[0] == '中' {
-            let chessman: Chessman = chars[1].into();
-            if let Some(coordinates) = layout.find_chessman(&chessman) {
-                let size = coordinates.len();
-                if !(size == 2 || size == 3) {
-                    return None;
-                }
-                let index = if size == 2 {
-                    if chars[0] == '前' {
-                        0
-                    } else if chars[0] == '后' {
-                        1
-                    } else {
-                        unreachable!()
-                    }
-                } else if size == 3 {
-                    if chars[0] == '前' {
-                        0
-                    } else if chars[0] == '中' {
-                        1
-                    } else if chars[0] == '后' {
-                        2
-                    } else {
-                        unreachable!()
-                    }
-                } else {
-                    unreachable!()
-                };
-                Some((chessman, coordinates[index]))
-            } else {
-                None
-            }
+    fn find_chessman(chars: &[char], layout: &Layout) -> Result<(Chessman, Square), ChessError> {
+        assert!(chars.len() == 4);
+        let not_found = || ChessError::NoSuchChessman(chars[..2].iter().collect());
+        // the last character tells the side: chinese numbers for red, digits for black
+        let color = if Self::C_NUMBER.contains(&chars[3]) {
+            Color::Red
         } else {
-            let mut chessman: Chessman = chars[0].into();
-            if chars[1].is_numeric() {
-                chessman.into_black();
-            } else if Self::C_NUMBER.contains(&chars[1]) {
-                chessman.into_red();
+            Color::Black
+        };
+        let with_color = |chessman: Chessman| Chessman::new(chessman.kind(), color);
+        if Self::C_CHESSMAN.contains(&chars[0]) {
+            let chessman = with_color(Chessman::try_from(chars[0])?);
+            let column = Self::classic_to_coordinate(&chars[1])?;
+            return layout
+                .find_chessman_at_column(&chessman, column)
+                .map(|from| (chessman, from))
+                .ok_or_else(not_found);
+        }
+
+        // chessmen sharing a column: 前/中/后, or 一二三四五 from front to back
+        // for a column of four or five pawns
+        let (chessman, coordinates) = if Self::C_CHESSMAN.contains(&chars[1]) {
+            let chessman = with_color(Chessman::try_from(chars[1])?);
+            let mut columns = layout.stacked_columns(&chessman);
+            if columns.len() != 1 {
+                return Err(not_found());
             }
-            let column = Self::classic_to_coordinate(&chars[1]);
-            if let Some(from) = layout.find_chessman_at_column(&chessman, &column) {
-                Some((chessman, from))
-            } else {
-                None
+            (chessman, columns.pop().unwrap())
+        } else {
+            // pawns stacked on several columns name the column instead of the chessman
+            let chessman = Chessman::new(PieceKind::Pawn, color);
+            let column = Self::classic_to_coordinate(&chars[1])?;
+            let coordinates = layout.front_to_back(&chessman, column);
+            (chessman, coordinates)
+        };
+        let size = coordinates.len();
+        let index = match chars[0] {
+            '前' if size == 2 || size == 3 => 0,
+            '中' if size == 3 => 1,
+            '后' if size == 2 || size == 3 => size - 1,
+            c if c.is_ascii_digit() || Self::C_NUMBER.contains(&c) => {
+                Self::classic_to_number(&c)? - 1
             }
-        }
+            '前' | '中' | '后' => return Err(not_found()),
+            c => return Err(ChessError::BadChessman(c)),
+        };
+        coordinates
+            .get(index)
+            .map(|from| (chessman, *from))
+            .ok_or_else(not_found)
     }
 
-    pub fn parse_input(&self, input: &str, layout: &Layout) -> Option<Move> {
+    pub fn parse_input(
+        &self,
+        input: &str,
+        layout: &Layout,
+        side: Color,
+    ) -> Result<Move, ChessError> {
         match self.mode {
             InputMode::Classic => {
                 let chars: Vec<char> = input.trim().chars().collect();
-                if chars.len() != 4 {
-                    eprintln!("input len is not equal to 4, got {}", chars.len());
-                    return None;
-                }
-                if let Some((chessman, from)) = Self::find_chessman(&chars[..2], layout) {
-                    let n = Self::classic_to_number(&chars[3]);
-                    let to = if chars[2] == '平' {
-                        let n = Self::classic_to_coordinate(&chars[3]);
-                        (n, from.1)
-                    } else if chars[2] == '进' && chessman.is_red()
-                        || chars[2] == '退' && chessman.is_black()
-                    {
-                        if chessman.is_move_straight() {
-                            (from.0, from.1 - n)
-                        } else {
-                            let n = Self::classic_to_coordinate(&chars[3]);
-                            if chessman.is_horse() {
-                                (n, from.1 - 2)
-                            } else if chessman.is_advisor() {
-                                (n, from.1 - 1)
-                            } else if chessman.is_elephant() {
-                                (n, from.1 - 2)
-                            } else {
-                                unreachable!()
-                            }
-                        }
-                    } else if chars[2] == '退' && chessman.is_red()
-                        || chars[2] == '进' && chessman.is_black()
-                    {
-                        if chessman.is_move_straight() {
-                            (from.0, from.1 + n)
-                        } else {
-                            let n = Self::classic_to_coordinate(&chars[3]);
-                            if chessman.is_horse() {
-                                if n.abs_diff(from.0) == 1 {
-                                    (n, from.1 + 2)
-                                } else if n.abs_diff(from.0) == 2 {
-                                    (n, from.1 + 1)
-                                } else {
-                                    eprintln!("invalid movement, move to far");
-                                    return None;
-                                }
-                            } else if chessman.is_advisor() {
-                                (n, from.1 + 1)
-                            } else if chessman.is_elephant() {
-                                (n, from.1 + 2)
-                            } else {
-                                unreachable!()
-                            }
-                        }
-                    } else {
-                        eprintln!("unsupported movement {}", chars[2]);
-                        return None;
-                    };
-                    let m = Move { from, to };
-                    if layout.is_valid_move(&m) {
-                        return Some(m);
-                    } else {
-                        eprintln!("invalid movement from {:?} to {:?}", from, to);
-                        return None;
-                    }
+                Self::parse_classic(&chars, layout)
+            }
+            InputMode::Iccs => {
+                let m = Move::from_iccs(input)
+                    .ok_or_else(|| ChessError::BadCoordinates(input.trim().to_string()))?;
+                if layout.is_valid_move(&m)? {
+                    Ok(m)
                 } else {
-                    eprintln!("cannot find chessman {:?}", &chars[..2]);
-                    return None;
+                    Err(ChessError::IllegalMove(m))
                 }
             }
+            InputMode::Wxf => {
+                let chars = Self::wxf_to_classic(input, side)?;
+                Self::parse_classic(&chars, layout)
+            }
         }
     }
 
-    fn classic_to_number(c: &char) -> usize {
-        if c.is_numeric() {
-            usize::from_str_radix(&format!("{c}"), 10).unwrap()
-        } else if Self::C_NUMBER.contains(c) {
-            Self::character_to_number(c)
-        } else {
-            panic!("unsupported number {c}");
+    // rewrite WXF such as "C2=5" or "+R-1" into the classic "炮二平五" / "前车退一",
+    // WXF doesn't tell the side so the numbers are written for `side`
+    fn wxf_to_classic(input: &str, side: Color) -> Result<Vec<char>, ChessError> {
+        let chars: Vec<char> = input.trim().chars().collect();
+        if chars.len() != 4 {
+            return Err(ChessError::BadLength(chars.len()));
         }
+        let number = |c: char| -> Result<char, ChessError> {
+            let n = c
+                .to_digit(10)
+                .filter(|n| *n != 0)
+                .ok_or(ChessError::BadNumber(c))? as usize;
+            match side {
+                Color::Red => Ok(Self::C_NUMBER[n - 1]),
+                Color::Black => Ok(c),
+            }
+        };
+        let chessman = |c: char| {
+            Self::WXF_CHESSMAN
+                .contains(&c.to_ascii_uppercase())
+                .then(|| Chessman::try_from(c.to_ascii_uppercase()).ok())
+                .flatten()
+                .map(|chessman| chessman.to_chinese_char())
+                .ok_or(ChessError::BadChessman(c))
+        };
+        Ok(vec![
+            match chars[0] {
+                '+' => '前',
+                '-' => '后',
+                c if c.is_ascii_digit() => number(c)?,
+                c => chessman(c)?,
+            },
+            if chars[1].is_ascii_digit() {
+                number(chars[1])?
+            } else {
+                chessman(chars[1])?
+            },
+            match chars[2] {
+                '+' => '进',
+                '-' => '退',
+                '=' | '.' => '平',
+                c => return Err(ChessError::BadDirection(c)),
+            },
+            number(chars[3])?,
+        ])
     }
-    fn classic_to_coordinate(c: &char) -> usize {
-        if c.is_numeric() {
-            usize::from_str_radix(&format!("{c}"), 10).unwrap() - 1
-        } else if Self::C_NUMBER.contains(c) {
-            let n = Self::character_to_number(c);
-            9 - n
+
+    fn parse_classic(chars: &[char], layout: &Layout) -> Result<Move, ChessError> {
+        if chars.len() != 4 {
+            return Err(ChessError::BadLength(chars.len()));
+        }
+        let (chessman, from) = Self::find_chessman(chars, layout)?;
+        let n = Self::classic_to_number(&chars[3])?;
+        let file = Self::classic_to_coordinate(&chars[3])?;
+        let to = if chars[2] == '平' {
+            Some(Square::new(file, from.rank()))
+        } else if chars[2] == '进' || chars[2] == '退' {
+            // red moves up the board when it advances, black moves down
+            let up = (chars[2] == '进') == (chessman.color() == Color::Red);
+            let step = |to: Square, d: usize| {
+                let d = d as isize;
+                to.offset(0, if up { d } else { -d })
+            };
+            if chessman.is_move_straight() {
+                step(from, n)
+            } else {
+                let d = if chessman.is_horse() {
+                    match file.index().abs_diff(from.file().index()) {
+                        1 => 2,
+                        2 => 1,
+                        _ => 0,
+                    }
+                } else if chessman.is_advisor() {
+                    1
+                } else {
+                    2
+                };
+                step(Square::new(file, from.rank()), d).filter(|_| d != 0)
+            }
+        } else {
+            return Err(ChessError::BadDirection(chars[2]));
+        };
+        let to = to.ok_or(ChessError::TooFar)?;
+        let m = Move { from, to };
+        if layout.is_valid_move(&m)? {
+            Ok(m)
         } else {
-            panic!("unsupported number {c}");
+            Err(ChessError::IllegalMove(m))
         }
     }
 
-    fn character_to_number(c: &char) -> usize {
-        match c {
+    fn classic_to_number(c: &char) -> Result<usize, ChessError> {
+        match c.to_digit(10) {
+            Some(n) if n != 0 => Ok(n as usize),
+            Some(_) => Err(ChessError::BadNumber(*c)),
+            None => Self::character_to_number(c),
+        }
+    }
+    fn classic_to_coordinate(c: &char) -> Result<File, ChessError> {
+        // digits count files from black's right, chinese numbers from red's right
+        let (n, color) = if c.is_ascii_digit() {
+            (Self::classic_to_number(c)?, Color::Black)
+        } else {
+            (Self::character_to_number(c)?, Color::Red)
+        };
+        File::from_number(n, color).ok_or(ChessError::BadNumber(*c))
+    }
+
+    fn character_to_number(c: &char) -> Result<usize, ChessError> {
+        Ok(match c {
             '一' => 1,
             '二' => 2,
             '三' => 3,
@@ -202,7 +348,7 @@ impl Input {
             '七' => 7,
             '八' => 8,
             '九' => 9,
-            _ => panic!("unsupported number {c}"),
-        }
+            _ => return Err(ChessError::BadNumber(*c)),
+        })
     }
 }
@@ -1,59 +1,251 @@
 use core::fmt;
 
 use crate::{
-    chessman::{ChessDisplayMode, Chessman},
+    bitboard::{self, Bitboards},
+    chessman::{ChessDisplayMode, Chessman, PieceKind},
     constants::BOARD,
-    input::Move,
+    error::ChessError,
+    evaluate::EvalConfig,
+    input::{Input, Move},
+    square::{File, Rank, Square},
+    zobrist, Color,
 };
 
+/// The chessmen on the board, with the Zobrist hash, the bitboards and the
+/// move counters kept in step with them.
+#[derive(Clone, PartialEq)]
 pub struct Layout {
-    pub board: [[Option<Chessman>; 9]; 10],
+    // indexed by `Square::index`
+    board: [Option<Chessman>; 90],
     mode: ChessDisplayMode,
+    hash: u64,
+    bitboards: Bitboards,
+    counters: Counters,
+}
+
+/// The move counters of a FEN, kept up to date by `make_move`.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct Counters {
+    /// Plies played since the last capture.
+    pub peace_round: usize,
+    /// The full move number, raised after every black move.
+    pub round: usize,
+}
+
+/// What `make_move` overwrites, see `unmake_move`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct UndoInfo {
+    /// The move that was played.
+    pub m: Move,
+    /// The chessman it took, if any.
+    pub captured: Option<Chessman>,
+    /// `Layout::hash` before the move.
+    pub hash: u64,
+    /// The counters before the move.
+    pub counters: Counters,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Layout {
-    const HEIGHT: usize = 10;
-    const WIDTH: usize = 9;
+    /// An empty board.
     pub fn new() -> Self {
         Self {
-            board: [[None; 9]; 10],
+            board: [None; Square::COUNT],
             mode: ChessDisplayMode::Character,
+            hash: 0,
+            bitboards: Bitboards::new(),
+            counters: Counters::default(),
         }
     }
-    pub fn find_chessman_at_column(
+
+    /// The move counters, see `Counters`.
+    pub fn counters(&self) -> Counters {
+        self.counters
+    }
+
+    pub(crate) fn set_counters(&mut self, counters: Counters) {
+        self.counters = counters;
+    }
+
+    /// Zobrist key of the chessmen and the side to move, kept up to date by
+    /// `set`, `handle_move` and `make_move`.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    pub(crate) fn reset_hash(&mut self, side: Color) {
+        self.hash = if side == Color::Black {
+            zobrist::SIDE_KEY
+        } else {
+            0
+        };
+        self.bitboards = Bitboards::new();
+        for square in Square::all() {
+            if let Some(c) = self.get(square) {
+                self.hash ^= zobrist::piece_key(&c, square);
+                self.bitboards.toggle(&c, square);
+            }
+        }
+    }
+
+    /// Put `chessman` on `square`, or empty it.
+    pub fn set(&mut self, square: Square, chessman: Option<Chessman>) {
+        let old = std::mem::replace(&mut self.board[square.index()], chessman);
+        if let Some(c) = old {
+            self.hash ^= zobrist::piece_key(&c, square);
+            self.bitboards.toggle(&c, square);
+        }
+        if let Some(c) = chessman {
+            self.hash ^= zobrist::piece_key(&c, square);
+            self.bitboards.toggle(&c, square);
+        }
+    }
+    pub(crate) fn find_chessman_at_column(
         &self,
         chessman: &Chessman,
-        column: &usize,
-    ) -> Option<(usize, usize)> {
-        for line in 0..10 {
-            if let Some(c) = self.board[line][*column] {
-                if c == *chessman {
-                    return Some((*column, line));
-                }
+        file: File,
+    ) -> Option<Square> {
+        Rank::all()
+            .rev()
+            .map(|rank| Square::new(file, rank))
+            .find(|square| self.get(*square) == Some(*chessman))
+    }
+    pub(crate) fn find_chessman(&self, chessman: &Chessman) -> Option<Vec<Square>> {
+        Some(bitboard::squares(self.bitboards.pieces(chessman)).collect())
+    }
+
+    /// Chessmen of one kind on `file`, ordered from the front to the back
+    /// as their owner sees the board.
+    pub(crate) fn front_to_back(&self, chessman: &Chessman, file: File) -> Vec<Square> {
+        let mut res: Vec<Square> = Rank::all()
+            .map(|rank| Square::new(file, rank))
+            .filter(|square| self.get(*square) == Some(*chessman))
+            .collect();
+        res.sort_by_key(|square| std::cmp::Reverse(square.rank().relative(chessman.color())));
+        res
+    }
+
+    /// Files holding more than one chessman of this kind, see `front_to_back`.
+    pub(crate) fn stacked_columns(&self, chessman: &Chessman) -> Vec<Vec<Square>> {
+        File::all()
+            .map(|file| self.front_to_back(chessman, file))
+            .filter(|squares| squares.len() > 1)
+            .collect()
+    }
+
+    /// Write a move in traditional chinese notation such as "炮二平五",
+    /// the reverse of `Input::parse_input`.
+    pub fn to_chinese_notation(&self, m: &Move) -> Result<String, ChessError> {
+        let Some(chessman) = self.get(m.from) else {
+            return Err(ChessError::EmptySquare(m.from));
+        };
+        let number = |n: usize| {
+            if chessman.color() == Color::Red {
+                Input::C_NUMBER[n - 1]
+            } else {
+                char::from_digit(n as u32, 10).unwrap()
+            }
+        };
+        // files are counted from each side's right hand
+        let column = |square: Square| square.file().number(chessman.color());
+
+        let mut res = String::new();
+        let same_column = self.front_to_back(&chessman, m.from.file());
+        if same_column.len() > 1 {
+            let index = same_column.iter().position(|c| *c == m.from).unwrap();
+            let size = same_column.len();
+            res.push(match (index, size) {
+                (0, 2) | (0, 3) => '前',
+                (1, 3) => '中',
+                (_, 2) | (_, 3) => '后',
+                _ => number(index + 1),
+            });
+            if chessman.is_pawn() && self.stacked_columns(&chessman).len() > 1 {
+                res.push(number(column(m.from)));
+            } else {
+                res.push(chessman.to_chinese_char());
+            }
+        } else {
+            res.push(chessman.to_chinese_char());
+            res.push(number(column(m.from)));
+        }
+
+        if m.from.rank() == m.to.rank() {
+            res.push('平');
+            res.push(number(column(m.to)));
+        } else {
+            res.push(if chessman.is_move_forward(m) {
+                '进'
+            } else {
+                '退'
+            });
+            if chessman.is_move_straight() {
+                res.push(number(m.from.rank().index().abs_diff(m.to.rank().index())));
+            } else {
+                res.push(number(column(m.to)));
             }
         }
-        None
+        Ok(res)
     }
-    pub fn find_chessman(&self, chessman: &Chessman) -> Option<Vec<(usize, usize)>> {
-        let mut res = vec![];
-        for line in 0..10 {
-            for col in 0..9 {
-                if let Some(c) = self.board[line][col] {
-                    if c == *chessman {
-                        res.push((col, line));
-                    }
-                }
+
+    /// Write a move in WXF notation such as "C2=5" or "+R-1". Two chessmen
+    /// on one column are told apart by `+`/`-`, three or more pawns by their
+    /// order from the front, `1P`, `2P` and so on.
+    pub fn to_wxf_notation(&self, m: &Move) -> Result<String, ChessError> {
+        let Some(chessman) = self.get(m.from) else {
+            return Err(ChessError::EmptySquare(m.from));
+        };
+        let number = |n: usize| char::from_digit(n as u32, 10).unwrap();
+        let column = |square: Square| square.file().number(chessman.color());
+
+        let mut res = String::new();
+        let same_column = self.front_to_back(&chessman, m.from.file());
+        if same_column.len() > 1 {
+            let index = same_column.iter().position(|c| *c == m.from).unwrap();
+            res.push(match (index, same_column.len()) {
+                (0, 2) => '+',
+                (_, 2) => '-',
+                _ => number(index + 1),
+            });
+            if chessman.is_pawn() && self.stacked_columns(&chessman).len() > 1 {
+                res.push(number(column(m.from)));
+            } else {
+                res.push(chessman.to_wxf_char());
             }
+        } else {
+            res.push(chessman.to_wxf_char());
+            res.push(number(column(m.from)));
         }
-        Some(res)
+
+        if m.from.rank() == m.to.rank() {
+            res.push('=');
+            res.push(number(column(m.to)));
+        } else {
+            res.push(if chessman.is_move_forward(m) {
+                '+'
+            } else {
+                '-'
+            });
+            if chessman.is_move_straight() {
+                res.push(number(m.from.rank().index().abs_diff(m.to.rank().index())));
+            } else {
+                res.push(number(column(m.to)));
+            }
+        }
+        Ok(res)
     }
 
     pub fn to_fen_string(&self) -> String {
         let mut res = "".to_string();
         let mut cnt = 0u8;
-        for line in 0..10 {
-            for col in 0..9 {
-                if let Some(c) = self.board[line][col] {
+        for line in 0..Rank::COUNT {
+            for col in 0..File::COUNT {
+                if let Some(c) = self.board[line * File::COUNT + col] {
                     if cnt != 0 {
                         res.push((0x30 + cnt) as char);
                         cnt = 0;
@@ -74,223 +266,433 @@ impl Layout {
         res
     }
 
-    pub fn handle_move(&mut self, m: &Move) {
-        let position = self.get_mut(m.from.1, m.from.0);
-        if position.is_none() {
-            // FIXME: find a better way to print coordinate
-            panic!("cannot find chessman on position {:?}", m.from);
+    /// Move the chessman on `m.from` without checking the rules.
+    pub fn handle_move(&mut self, m: &Move) -> Result<(), ChessError> {
+        self.make_move(m)?;
+        Ok(())
+    }
+
+    /// Play `m` like `handle_move`, keeping what `unmake_move` needs to
+    /// restore the position.
+    pub fn make_move(&mut self, m: &Move) -> Result<UndoInfo, ChessError> {
+        if self.get(m.from).is_none() {
+            return Err(ChessError::EmptySquare(m.from));
+        }
+        Ok(self.make_move_unchecked(m))
+    }
+
+    // `make_move` for moves known to start on a chessman, as every move
+    // from `legal_moves` does
+    pub(crate) fn make_move_unchecked(&mut self, m: &Move) -> UndoInfo {
+        debug_assert!(self.get(m.from).is_some(), "no chessman on {:?}", m.from);
+        let undo = UndoInfo {
+            m: *m,
+            captured: self.get(m.to),
+            hash: self.hash,
+            counters: self.counters,
+        };
+        let chessman = self.get(m.from);
+        self.set(m.from, None);
+        self.set(m.to, chessman);
+        self.hash ^= zobrist::SIDE_KEY;
+        if undo.captured.is_some() {
+            self.counters.peace_round = 0;
+        } else {
+            self.counters.peace_round += 1;
         }
-        let chessman = position.clone().unwrap();
-        *position = None;
-        let position = self.get_mut(m.to.1, m.to.0);
-        *position = Some(chessman);
+        if chessman.is_some_and(|c| c.color() == Color::Black) {
+            self.counters.round += 1;
+        }
+        undo
+    }
+
+    /// Take back the move `undo` was made for, which has to be the last one
+    /// played on this layout.
+    pub fn unmake_move(&mut self, undo: &UndoInfo) {
+        let chessman = self.get(undo.m.to);
+        self.set(undo.m.from, chessman);
+        self.set(undo.m.to, undo.captured);
+        self.hash = undo.hash;
+        self.counters = undo.counters;
+    }
+
+    /// Whether `m` follows the rules and doesn't leave the mover's king in
+    /// check.
+    pub fn is_valid_move(&self, m: &Move) -> Result<bool, ChessError> {
+        let Some(chessman) = self.get(m.from) else {
+            return Err(ChessError::EmptySquare(m.from));
+        };
+        if !self.is_pseudo_valid_move(m)? {
+            return Ok(false);
+        }
+        let mut layout = self.clone();
+        layout.make_move_unchecked(m);
+        Ok(!layout.is_in_check(chessman.color()))
+    }
+
+    /// Where the king of `side` stands, if it is on the board.
+    pub fn find_king(&self, side: Color) -> Option<Square> {
+        let king = Chessman::new(PieceKind::King, side);
+        bitboard::squares(self.bitboards.pieces(&king)).next()
+    }
+
+    /// Whether the king of `side` is attacked.
+    pub fn is_in_check(&self, side: Color) -> bool {
+        match self.find_king(side) {
+            Some(king) => self.is_attacked(king, side.opponent()),
+            None => false,
+        }
+    }
+
+    /// Whether any chessman of `by` could capture on `square`,
+    /// ignoring whether that capture would expose its own king.
+    /// Generals facing each other on an open file count as attacking each other.
+    pub fn is_attacked(&self, square: Square, by: Color) -> bool {
+        if self.is_facing_king(square, by) {
+            return true;
+        }
+        let target = bitboard::bit(square);
+        if self.bitboards.side(by) & target != 0 {
+            return false;
+        }
+        let occupied = self.bitboards.occupied();
+        bitboard::squares(self.bitboards.side(by)).any(|from| {
+            let chessman = self.get(from).unwrap();
+            bitboard::attacks(&chessman, from, occupied) & target != 0
+        })
+    }
+
+    /// `is_attacked` going through every square, to check the bitboards.
+    #[cfg(test)]
+    pub(crate) fn is_attacked_by_scan(&self, square: Square, by: Color) -> bool {
+        if self.is_facing_king(square, by) {
+            return true;
+        }
+        Square::all().any(|from| {
+            let m = Move { from, to: square };
+            self.get(from).is_some_and(|c| by.owns(&c))
+                && from != square
+                && self.is_pseudo_valid_move(&m) == Ok(true)
+        })
+    }
+
+    /// Count the leaf nodes of the legal move tree `depth` plies deep.
+    pub fn perft(&self, side: Color, depth: usize) -> usize {
+        self.clone().perft_inner(side, depth)
     }
 
-    pub fn is_valid_move(&self, m: &Move) -> bool {
-        let chessman = self
-            .get_at(m.from)
-            .expect(&format!("cannot find a chessman at position {:?}", m.from));
+    /// `perft` split by the first move, to find where two move generators differ.
+    pub fn divide(&self, side: Color, depth: usize) -> Vec<(Move, usize)> {
+        let mut layout = self.clone();
+        let mut res = vec![];
+        for m in self.legal_moves(side) {
+            let undo = layout.make_move_unchecked(&m);
+            res.push((
+                m,
+                layout.perft_inner(side.opponent(), depth.saturating_sub(1)),
+            ));
+            layout.unmake_move(&undo);
+        }
+        res
+    }
+
+    fn perft_inner(&mut self, side: Color, depth: usize) -> usize {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.legal_moves(side);
+        if depth == 1 {
+            return moves.len();
+        }
+        let mut res = 0;
+        for m in moves {
+            let undo = self.make_move_unchecked(&m);
+            res += self.perft_inner(side.opponent(), depth - 1);
+            self.unmake_move(&undo);
+        }
+        res
+    }
+
+    /// Static exchange evaluation: the material `m`'s side wins or loses if
+    /// both sides keep recapturing on the target square with their least
+    /// valuable chessman. Every capture is checked on the updated board, so
+    /// cannons gain or lose their screens as chessmen leave the line.
+    pub fn see(&self, m: &Move, config: &EvalConfig) -> Result<i32, ChessError> {
+        // the king only recaptures if nothing can take it back
+        let value = |c: &Chessman| {
+            if c.is_king() {
+                10000
+            } else {
+                config.material(c)
+            }
+        };
+        let Some(mover) = self.get(m.from) else {
+            return Err(ChessError::EmptySquare(m.from));
+        };
+        let mut layout = self.clone();
+        let mut side = mover.color().opponent();
+        let mut gain = vec![layout.get(m.to).map_or(0, |c| value(&c))];
+        let mut on_square = value(&mover);
+        layout.make_move_unchecked(m);
+        while let Some(from) = layout.least_valuable_attacker(m.to, side, &value) {
+            gain.push(on_square - gain.last().unwrap());
+            on_square = value(&layout.get(from).unwrap());
+            layout.make_move_unchecked(&Move { from, to: m.to });
+            side = side.opponent();
+        }
+        // either side may stop recapturing when it doesn't pay
+        while gain.len() > 1 {
+            let last = gain.pop().unwrap();
+            let prev = gain.last_mut().unwrap();
+            *prev = -(-*prev).max(last);
+        }
+        Ok(gain[0])
+    }
+
+    fn least_valuable_attacker(
+        &self,
+        square: Square,
+        by: Color,
+        value: &impl Fn(&Chessman) -> i32,
+    ) -> Option<Square> {
+        let mut res: Option<(Square, i32)> = None;
+        for from in Square::all() {
+            let chessman = match self.get(from) {
+                Some(c) if by.owns(&c) => c,
+                _ => continue,
+            };
+            let m = Move { from, to: square };
+            if res.is_some_and(|(_, v)| v <= value(&chessman)) {
+                continue;
+            }
+            if self.is_pseudo_valid_move(&m) == Ok(true) {
+                res = Some((m.from, value(&chessman)));
+            }
+        }
+        res.map(|(from, _)| from)
+    }
+
+    fn is_facing_king(&self, square: Square, by: Color) -> bool {
+        match self.get(square) {
+            Some(c) if c.is_king() && !by.owns(&c) => {}
+            _ => return false,
+        }
+        match self.find_king(by) {
+            Some(king) if king.file() == square.file() => {
+                let m = Move {
+                    from: king,
+                    to: square,
+                };
+                self.get_bypass_chessman_num(&m) == 0
+            }
+            _ => false,
+        }
+    }
+
+    // checks how the chessman moves, but not whether the move leaves its king in check
+    fn is_pseudo_valid_move(&self, m: &Move) -> Result<bool, ChessError> {
+        let chessman = self.get(m.from).ok_or(ChessError::EmptySquare(m.from))?;
         let from = m.from;
         let to = m.to;
-        let position = self.get_at(m.to);
+        let position = self.get(m.to);
         if let Some(c) = position {
-            if !chessman.is_different_color(&c) {
-                return false;
+            if chessman.color() == c.color() {
+                return Ok(false);
             }
         }
         if chessman.is_pawn() {
             if chessman.is_cross_river(&from) {
                 if chessman.is_move_backward(m) {
-                    return false;
+                    return Ok(false);
                 }
             } else {
                 if chessman.is_move_horizontally(m) || chessman.is_move_backward(m) {
-                    return false;
+                    return Ok(false);
                 }
             }
             if !chessman.is_move_one_step(m) {
-                return false;
+                return Ok(false);
             }
         } else if chessman.is_cannon() {
-            let move_in_horizontal = from.1.abs_diff(to.1);
-            let move_in_vertical = from.0.abs_diff(to.0);
-            if move_in_horizontal != 0 && move_in_vertical != 0 {
-                return false;
+            let ranks = from.rank().index().abs_diff(to.rank().index());
+            let files = from.file().index().abs_diff(to.file().index());
+            if ranks != 0 && files != 0 {
+                return Ok(false);
             }
             let bypass_num = self.get_bypass_chessman_num(m);
-            if bypass_num != 0 && bypass_num != 2 {
-                return false;
+            if position.is_some() && bypass_num != 1 || position.is_none() && bypass_num != 0 {
+                return Ok(false);
             }
         } else if chessman.is_rook() {
-            let move_in_horizontal = from.1.abs_diff(to.1);
-            let move_in_vertical = from.0.abs_diff(to.0);
-            if move_in_horizontal != 0 && move_in_vertical != 0 {
-                return false;
+            let ranks = from.rank().index().abs_diff(to.rank().index());
+            let files = from.file().index().abs_diff(to.file().index());
+            if ranks != 0 && files != 0 {
+                return Ok(false);
             }
-            if self.get_bypass_chessman_num(m) > 1 {
-                return false;
+            if self.get_bypass_chessman_num(m) != 0 {
+                return Ok(false);
             }
         } else if chessman.is_horse() {
-            let move_in_horizontal = from.1.abs_diff(to.1);
-            let move_in_vertical = from.0.abs_diff(to.0);
-            if !((move_in_horizontal == 1 && move_in_vertical == 2)
-                || (move_in_horizontal == 2 && move_in_vertical == 1))
-            {
-                return false;
-            }
-            if move_in_horizontal == 2 {
-                if chessman.is_move_backward(m) {
-                    let back_position = self.get_back_of_chessman(from, &chessman);
-                    if back_position.is_some() {
-                        return false;
-                    }
-                } else if chessman.is_move_forward(m) {
-                    let front_position = self.get_front_of_chessman(from, &chessman);
-                    if front_position.is_some() {
-                        return false;
-                    }
-                }
-            } else if move_in_horizontal == 1 {
-                if chessman.is_move_left(m) {
-                    let position = self.get_left_of_chessman(from, &chessman);
-                    if position.is_some() {
-                        return false;
-                    }
-                } else if chessman.is_move_right(m) {
-                    let position = self.get_right_of_chessman(from, &chessman);
-                    if position.is_some() {
-                        return false;
-                    }
-                }
+            let ranks = from.rank().index().abs_diff(to.rank().index());
+            let files = from.file().index().abs_diff(to.file().index());
+            if !((ranks == 1 && files == 2) || (ranks == 2 && files == 1)) {
+                return Ok(false);
+            }
+            // the leg is next to the horse in the direction of the long step
+            let leg = if ranks == 2 {
+                Square::new(
+                    from.file(),
+                    Rank::new((from.rank().index() + to.rank().index()) / 2).unwrap(),
+                )
+            } else {
+                Square::new(
+                    File::new((from.file().index() + to.file().index()) / 2).unwrap(),
+                    from.rank(),
+                )
+            };
+            if self.get(leg).is_some() {
+                return Ok(false);
             }
         } else if chessman.is_elephant() {
-            let move_in_horizontal = from.1 as i8 - to.1 as i8;
-            let move_in_vertical = from.0 as i8 - to.0 as i8;
-            if move_in_horizontal.abs() != 2 || move_in_vertical.abs() != 2 {
-                return false;
-            }
-            let n_cor = (
-                from.0 as i8 + move_in_horizontal / 2,
-                from.1 as i8 + move_in_vertical / 2,
-            );
-            if !Self::is_valid_coordinate(&n_cor) {
-                return false;
+            let ranks = from.rank().index().abs_diff(to.rank().index());
+            let files = from.file().index().abs_diff(to.file().index());
+            if ranks != 2 || files != 2 {
+                return Ok(false);
             }
-            let n_cor = (n_cor.0 as usize, n_cor.1 as usize);
-            let position = self.get_at(n_cor);
-            if position.is_some() {
-                return false;
+            if chessman.is_cross_river(&to) {
+                return Ok(false);
+            }
+            let eye = Square::new(
+                File::new((from.file().index() + to.file().index()) / 2).unwrap(),
+                Rank::new((from.rank().index() + to.rank().index()) / 2).unwrap(),
+            );
+            if self.get(eye).is_some() {
+                return Ok(false);
             }
         } else if chessman.is_advisor() {
             if !chessman.is_in_palace(&to) {
-                return false;
+                return Ok(false);
             }
-            let move_in_horizontal = from.1.abs_diff(to.1);
-            let move_in_vertical = from.0.abs_diff(to.0);
-            if move_in_horizontal != 1 || move_in_vertical != 1 {
-                return false;
+            let ranks = from.rank().index().abs_diff(to.rank().index());
+            let files = from.file().index().abs_diff(to.file().index());
+            if ranks != 1 || files != 1 {
+                return Ok(false);
             }
         } else if chessman.is_king() {
             if !chessman.is_in_palace(&to) {
-                return false;
+                return Ok(false);
             }
             if !chessman.is_move_one_step(m) {
-                return false;
+                return Ok(false);
             }
         }
-        true
+        Ok(true)
     }
 
-    fn is_valid_coordinate(coordinate: &(i8, i8)) -> bool {
-        coordinate.0 >= 0
-            && coordinate.0 < Self::WIDTH as i8
-            && coordinate.1 >= 0
-            && coordinate.1 < Self::HEIGHT as i8
-    }
-
-    fn get_bypass_chessman_num(&self, m: &Move) -> usize {
-        let mut res = 0;
-        let i = m.from.0;
-        for j in m.from.1 + 1..=m.to.1 {
-            if self.get(j, i).is_some() {
-                res += 1;
-            }
-        }
-        let j = m.from.1;
-        for i in m.from.0 + 1..=m.to.0 {
-            if self.get(j, i).is_some() {
-                res += 1;
+    /// List every legal move of `side` in the current layout.
+    pub fn legal_moves(&self, side: Color) -> Vec<Move> {
+        let mut res = vec![];
+        let mut layout = self.clone();
+        let occupied = self.bitboards.occupied();
+        let own = self.bitboards.side(side);
+        for from in bitboard::squares(own) {
+            let chessman = self.get(from).unwrap();
+            for to in bitboard::squares(bitboard::attacks(&chessman, from, occupied) & !own) {
+                let m = Move { from, to };
+                let undo = layout.make_move_unchecked(&m);
+                if !layout.is_in_check(side) {
+                    res.push(m);
+                }
+                layout.unmake_move(&undo);
             }
         }
         res
     }
 
-    fn get_right_of_chessman(
-        &self,
-        coordinate: (usize, usize),
-        chessman: &Chessman,
-    ) -> Option<Chessman> {
-        self.get_left_of_chessman(coordinate, &chessman.change_color())
-    }
-    fn get_left_of_chessman(
-        &self,
-        coordinate: (usize, usize),
-        chessman: &Chessman,
-    ) -> Option<Chessman> {
-        let col = coordinate.0;
-        if chessman.is_red() {
-            if col == 0 {
-                Some(Chessman::BOARD)
-            } else {
-                self.get_at((coordinate.0 - 1, coordinate.1))
-            }
-        } else {
-            if col == Self::WIDTH - 1 {
-                Some(Chessman::BOARD)
-            } else {
-                self.get_at((coordinate.0 + 1, coordinate.1))
+    /// `legal_moves` trying every reachable square with `is_pseudo_valid_move`
+    /// and `is_attacked_by_scan`, to check the bitboards.
+    #[cfg(test)]
+    pub(crate) fn legal_moves_by_scan(&self, side: Color) -> Vec<Move> {
+        let mut res = vec![];
+        for from in Square::all() {
+            let chessman = match self.get(from) {
+                Some(c) if side.owns(&c) => c,
+                _ => continue,
+            };
+            for to in Self::candidate_targets(&chessman, from) {
+                let m = Move { from, to };
+                if self.is_pseudo_valid_move(&m) != Ok(true) {
+                    continue;
+                }
+                let mut layout = self.clone();
+                layout.make_move_unchecked(&m);
+                let king = layout.find_king(side).unwrap();
+                if !layout.is_attacked_by_scan(king, side.opponent()) {
+                    res.push(m);
+                }
             }
         }
+        res
     }
-    fn get_back_of_chessman(
-        &self,
-        coordinate: (usize, usize),
-        chessman: &Chessman,
-    ) -> Option<Chessman> {
-        self.get_front_of_chessman(coordinate, &chessman.change_color())
-    }
-    fn get_front_of_chessman(
-        &self,
-        coordinate: (usize, usize),
-        chessman: &Chessman,
-    ) -> Option<Chessman> {
-        let line = coordinate.1;
-        if chessman.is_red() {
-            if line == 0 {
-                Some(Chessman::BOARD)
-            } else {
-                self.get_at((coordinate.0, line - 1))
-            }
-        } else {
-            if line == Self::HEIGHT - 1 {
-                Some(Chessman::BOARD)
-            } else {
-                self.get_at((coordinate.0, line + 1))
-            }
+
+    // every on-board square the chessman could reach on an empty board,
+    // `is_pseudo_valid_move` filters out the blocked ones
+    #[cfg(test)]
+    fn candidate_targets(chessman: &Chessman, from: Square) -> Vec<Square> {
+        if chessman.is_rook() || chessman.is_cannon() {
+            return Square::all()
+                .filter(|to| *to != from && (to.file() == from.file() || to.rank() == from.rank()))
+                .collect();
         }
+        let offsets: &[(isize, isize)] = if chessman.is_king() || chessman.is_pawn() {
+            &[(0, 1), (0, -1), (1, 0), (-1, 0)]
+        } else if chessman.is_advisor() {
+            &[(1, 1), (1, -1), (-1, 1), (-1, -1)]
+        } else if chessman.is_elephant() {
+            &[(2, 2), (2, -2), (-2, 2), (-2, -2)]
+        } else if chessman.is_horse() {
+            &[
+                (1, 2),
+                (1, -2),
+                (-1, 2),
+                (-1, -2),
+                (2, 1),
+                (2, -1),
+                (-2, 1),
+                (-2, -1),
+            ]
+        } else {
+            &[]
+        };
+        offsets
+            .iter()
+            .filter_map(|(x, y)| from.offset(*x, *y))
+            .collect()
     }
 
-    pub fn get_mut_at(&mut self, coordinate: (usize, usize)) -> &mut Option<Chessman> {
-        self.get_mut(coordinate.1, coordinate.0)
-    }
-    pub fn get_at(&self, coordinate: (usize, usize)) -> Option<Chessman> {
-        self.get(coordinate.1, coordinate.0)
-    }
-    pub fn get(&self, line: usize, column: usize) -> Option<Chessman> {
-        self.board[line][column]
+    /// Number of chessmen strictly between the two ends of a straight move.
+    pub(crate) fn get_bypass_chessman_num(&self, m: &Move) -> usize {
+        let (from, to) = (m.from, m.to);
+        let between = |a: usize, b: usize| a.min(b) + 1..a.max(b);
+        let squares: Vec<Square> = if from.file() == to.file() {
+            between(from.rank().index(), to.rank().index())
+                .filter_map(Rank::new)
+                .map(|rank| Square::new(from.file(), rank))
+                .collect()
+        } else if from.rank() == to.rank() {
+            between(from.file().index(), to.file().index())
+                .filter_map(File::new)
+                .map(|file| Square::new(file, from.rank()))
+                .collect()
+        } else {
+            vec![]
+        };
+        squares.iter().filter(|s| self.get(**s).is_some()).count()
     }
-    pub fn get_mut(&mut self, line: usize, column: usize) -> &mut Option<Chessman> {
-        &mut self.board[line][column]
+
+    /// The chessman on `square`, if any.
+    pub fn get(&self, square: Square) -> Option<Chessman> {
+        self.board[square.index()]
     }
 }
 
@@ -302,7 +704,7 @@ impl fmt::Display for Layout {
         let board: Vec<&str> = board.split('\n').collect();
         writeln!(f, "1   2   3   4   5   6   7   8   9").unwrap();
 
-        for j in 0..height {
+        for (j, board_line) in board.iter().enumerate().take(height) {
             let mut has_chessman = false;
             for i in 0..width {
                 let is_chessman = i % 4 == 0 && j % 2 == 0;
@@ -313,17 +715,17 @@ impl fmt::Display for Layout {
                 if is_chessman {
                     let x = i / 4;
                     let y = j / 2;
-                    if let Some(c) = self.board[y][x] {
+                    if let Some(c) = self.board[y * File::COUNT + x] {
                         has_chessman = true;
                         write!(f, "{}", c.to_string(self.mode)).unwrap();
                         if self.mode == ChessDisplayMode::Unicode {
                             write!(f, " ").unwrap();
                         }
                     } else {
-                        write!(f, "{}", board[j].chars().nth(i).unwrap()).unwrap();
+                        write!(f, "{}", board_line.chars().nth(i).unwrap()).unwrap();
                     }
                 } else {
-                    write!(f, "{}", board[j].chars().nth(i).unwrap()).unwrap();
+                    write!(f, "{}", board_line.chars().nth(i).unwrap()).unwrap();
                 }
             }
             writeln!(f).unwrap();
@@ -0,0 +1,1400 @@
+//! Xiangqi rules, notation and engine.
+//!
+//! [`Game`] keeps a game going: it loads FEN, plays and takes back moves,
+//! searches for the best move and tells when the game is over. Moves are
+//! given as [`Move`]s between [`Square`]s, or parsed from text in the
+//! notation chosen with [`InputMode`]. [`run_ucci`] and [`run_uci`] serve
+//! a game to engine GUIs.
+//!
+//! ```
+//! use chess::{Game, Move};
+//!
+//! let mut game = Game::new();
+//! let m = Move::from_iccs("h2e2").unwrap();
+//! game.play(&m).unwrap();
+//! assert_eq!(game.game_result(), None);
+//! ```
+
+mod bitboard;
+mod chessman;
+mod constants;
+mod error;
+mod evaluate;
+mod fen;
+mod input;
+mod layout;
+mod rules;
+mod search;
+mod square;
+mod tt;
+mod ucci;
+mod uci;
+mod zobrist;
+
+pub use chessman::{Chessman, Color, PieceKind};
+pub use error::ChessError;
+pub use evaluate::EvalConfig;
+pub use fen::FenError;
+pub use input::{Command, InputMode, Move};
+pub use layout::{Counters, Layout, UndoInfo};
+pub use rules::RepetitionRule;
+pub use search::{Limits, SearchResult};
+pub use square::{File, Rank, Square};
+pub use ucci::run as run_ucci;
+pub use uci::run as run_uci;
+
+use core::fmt;
+
+use constants::START_FEN;
+use input::Input;
+use rules::MoveKind;
+
+/// A game in progress: the position, the side to move, the move counters
+/// and the moves played so far.
+pub struct Game {
+    turn: Color,
+    layout: Layout,
+    input: Input,
+    history: Vec<Record>,
+    undone: Vec<Record>,
+    repetition_rule: RepetitionRule,
+    // plies without a capture before the game is drawn
+    move_limit: usize,
+    // the evaluation weights the engine searches with
+    eval_config: EvalConfig,
+}
+
+/// A played move with everything needed to take it back.
+struct Record {
+    // its hash is the position before the move, to find repetitions
+    undo: UndoInfo,
+    side: Color,
+}
+
+/// How a game ended, see `Game::game_result`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameResult {
+    /// The side to move is in check without a legal move.
+    Checkmate { winner: Color },
+    /// The side to move has no legal move, which loses in xiangqi even when
+    /// not in check.
+    Stalemate { winner: Color },
+    /// The loser kept checking through a repetition.
+    PerpetualCheck { winner: Color },
+    /// The loser kept chasing an unprotected chessman through a repetition.
+    PerpetualChase { winner: Color },
+    /// A repetition where neither side or both sides break the rules.
+    Repetition,
+    /// Too many plies without a capture, see `Game::set_move_limit`.
+    MoveLimit,
+    /// Neither side has a chessman left that can cross the river.
+    InsufficientMaterial,
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameResult::Checkmate { winner } => write!(f, "checkmate, {:?} wins", winner),
+            GameResult::Stalemate { winner } => write!(f, "stalemate, {:?} wins", winner),
+            GameResult::PerpetualCheck { winner } => {
+                write!(f, "perpetual check, {:?} wins", winner)
+            }
+            GameResult::PerpetualChase { winner } => {
+                write!(f, "perpetual chase, {:?} wins", winner)
+            }
+            GameResult::Repetition => write!(f, "draw by repetition"),
+            GameResult::MoveLimit => write!(f, "draw by the move limit"),
+            GameResult::InsufficientMaterial => write!(f, "draw by insufficient material"),
+        }
+    }
+}
+
+impl Game {
+    /// A game at the start position with red to move.
+    pub fn new() -> Self {
+        let mut game = Self {
+            turn: Color::Red,
+            layout: Layout::new(),
+            input: Input::new(),
+            history: vec![],
+            undone: vec![],
+            repetition_rule: RepetitionRule::default(),
+            move_limit: rules::DEFAULT_MOVE_LIMIT,
+            eval_config: EvalConfig::default(),
+        };
+        game.parse_fen(START_FEN)
+            .expect("the initial position is valid");
+        game
+    }
+
+    /// Load a FEN, leaving the game untouched if it is malformed or
+    /// describes an impossible position.
+    pub fn parse_fen(&mut self, input: &str) -> Result<(), ChessError> {
+        let mut fields = input.split_whitespace();
+        let placement = fields.next().unwrap_or("");
+        let lines: Vec<&str> = placement.split('/').collect();
+        if lines.len() != 10 {
+            return Err(FenError::WrongRankCount(lines.len()).into());
+        }
+        let mut layout = Layout::new();
+        for (line_cnt, line) in lines.into_iter().enumerate() {
+            let mut column_cnt = 0;
+            for c in line.chars() {
+                if let Some(n) = c.to_digit(10).filter(|n| *n != 0) {
+                    column_cnt += n as usize;
+                } else {
+                    let chessman = Chessman::from_fen_char(c).ok_or(FenError::BadPiece(c))?;
+                    if column_cnt < 9 {
+                        let square = Square::from_index(line_cnt * 9 + column_cnt).unwrap();
+                        layout.set(square, Some(chessman));
+                    }
+                    column_cnt += 1;
+                }
+                if column_cnt > 9 {
+                    return Err(FenError::RankTooLong(line_cnt).into());
+                }
+            }
+            if column_cnt < 9 {
+                return Err(FenError::RankTooShort(line_cnt).into());
+            }
+        }
+        let turn = match fields.next() {
+            Some("w") | Some("r") => Color::Red,
+            Some("b") => Color::Black,
+            Some(s) => return Err(FenError::BadSide(s.to_string()).into()),
+            None => return Err(FenError::MissingSide.into()),
+        };
+        fen::validate_layout(&layout, turn)?;
+        // castling and en passant don't exist in xiangqi, the two fields are
+        // "-" when given at all, and the counters may be omitted
+        let rest: Vec<&str> = fields.collect();
+        let dashes = rest.iter().take(2).take_while(|s| **s == "-").count();
+        let counters = &rest[dashes..];
+        let parse_counter = |s: &str| -> Result<usize, FenError> {
+            s.parse().map_err(|_| FenError::BadCounter(s.to_string()))
+        };
+        let peace_round = counters.first().map_or(Ok(0), |s| parse_counter(s))?;
+        let round = counters.get(1).map_or(Ok(1), |s| parse_counter(s))?;
+        if counters.len() > 2 {
+            return Err(FenError::BadCounter(counters[2..].join(" ")).into());
+        }
+
+        self.layout = layout;
+        self.layout.reset_hash(turn);
+        self.layout.set_counters(Counters { peace_round, round });
+        self.turn = turn;
+        self.history.clear();
+        self.undone.clear();
+        Ok(())
+    }
+
+    /// Export the full FEN of the game, the reverse of `parse_fen`.
+    pub fn to_fen_string(&self) -> String {
+        let turn = match self.turn {
+            Color::Red => 'w',
+            Color::Black => 'b',
+        };
+        format!(
+            "{} {} - - {} {}",
+            self.layout.to_fen_string(),
+            turn,
+            self.peace_round(),
+            self.round()
+        )
+    }
+
+    /// The result of the game if the side to move cannot play on.
+    pub fn game_result(&self) -> Option<GameResult> {
+        if !self.layout.legal_moves(self.turn).is_empty() {
+            return self.repetition_result().or_else(|| self.draw_result());
+        }
+        let winner = self.turn.opponent();
+        if self.layout.is_in_check(self.turn) {
+            Some(GameResult::Checkmate { winner })
+        } else {
+            Some(GameResult::Stalemate { winner })
+        }
+    }
+
+    /// Set the number of plies without a capture after which the game is
+    /// drawn.
+    pub fn set_move_limit(&mut self, plies: usize) {
+        self.move_limit = plies;
+    }
+
+    fn draw_result(&self) -> Option<GameResult> {
+        if self.peace_round() >= self.move_limit {
+            Some(GameResult::MoveLimit)
+        } else if rules::insufficient_material(&self.layout) {
+            Some(GameResult::InsufficientMaterial)
+        } else {
+            None
+        }
+    }
+
+    /// Choose how perpetual check and chase are judged, the Asian rules
+    /// by default.
+    pub fn set_repetition_rule(&mut self, rule: RepetitionRule) {
+        self.repetition_rule = rule;
+    }
+
+    /// Tune the evaluation the engine searches with.
+    pub fn set_eval_config(&mut self, config: EvalConfig) {
+        self.eval_config = config;
+    }
+
+    // judge the cycle once the current position has occurred often enough,
+    // a capture can never be part of a repetition
+    fn repetition_result(&self) -> Option<GameResult> {
+        let hash = self.layout.hash();
+        let since_capture =
+            &self.history[self.history.len() - self.peace_round().min(self.history.len())..];
+        let occurrences: Vec<usize> = since_capture
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.undo.hash == hash)
+            .map(|(i, _)| i)
+            .collect();
+        if occurrences.len() + 1 < rules::REPETITION_COUNT {
+            return None;
+        }
+        // classifying needs the position before each move, so it waits until
+        // a repetition shows up and then takes the cycle back move by move
+        let mut layout = self.layout.clone();
+        let mut cycle: Vec<(Color, MoveKind)> = since_capture[occurrences[0]..]
+            .iter()
+            .rev()
+            .map(|r| {
+                layout.unmake_move(&r.undo);
+                (r.side, rules::classify(&layout, &r.undo.m))
+            })
+            .collect();
+        cycle.reverse();
+        Some(rules::adjudicate(&cycle, self.repetition_rule))
+    }
+
+    /// The current position.
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    /// Every legal move of the side to move.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        self.layout.legal_moves(self.turn)
+    }
+
+    /// Whether the side to move is in check.
+    pub fn is_in_check(&self) -> bool {
+        self.layout.is_in_check(self.turn)
+    }
+
+    /// The side to move.
+    pub fn turn(&self) -> Color {
+        self.turn
+    }
+
+    /// The full move number, as in the last field of a FEN.
+    pub fn round(&self) -> usize {
+        self.layout.counters().round
+    }
+
+    /// Plies played since the last capture.
+    pub fn peace_round(&self) -> usize {
+        self.layout.counters().peace_round
+    }
+
+    /// Choose the notation `parse_move` and `parse_command` read moves in.
+    pub fn set_input_mode(&mut self, mode: InputMode) {
+        self.input.set_mode(mode);
+    }
+
+    /// Parse a move for the side to move in the current notation, see
+    /// `set_input_mode`.
+    pub fn parse_move(&self, text: &str) -> Result<Move, ChessError> {
+        self.input.parse_input(text, &self.layout, self.turn)
+    }
+
+    /// Parse a line of the interactive loop, with moves in the current
+    /// notation.
+    pub fn parse_command(&self, line: &str) -> Result<Command, ChessError> {
+        self.input.parse_command(line, &self.layout, self.turn)
+    }
+
+    /// Play a move for the side to move, keeping `turn`, `round` and
+    /// `peace_round` up to date. Leaves the game alone and tells why if the
+    /// square is empty, the moved chessman belongs to the other side or the
+    /// move is illegal.
+    pub fn play(&mut self, m: &Move) -> Result<(), ChessError> {
+        match self.layout.get(m.from) {
+            Some(c) if self.turn.owns(&c) => {}
+            Some(_) => return Err(ChessError::WrongSide(self.turn)),
+            None => return Err(ChessError::EmptySquare(m.from)),
+        }
+        if !self.layout.is_valid_move(m)? {
+            return Err(ChessError::IllegalMove(*m));
+        }
+        self.undone.clear();
+        self.apply(m);
+        Ok(())
+    }
+
+    fn apply(&mut self, m: &Move) {
+        let undo = self.layout.make_move_unchecked(m);
+        self.history.push(Record {
+            undo,
+            side: self.turn,
+        });
+        self.turn = self.turn.opponent();
+    }
+
+    /// Search the best move for the side to move within `limits`.
+    pub fn search(&self, limits: &Limits) -> SearchResult {
+        search::search(&self.layout, self.turn, limits, &self.eval_config)
+    }
+
+    /// Write `m` in traditional chinese notation such as "炮二平五".
+    pub fn to_chinese_notation(&self, m: &Move) -> Result<String, ChessError> {
+        self.layout.to_chinese_notation(m)
+    }
+
+    /// Write `m` in WXF notation such as "C2=5".
+    pub fn to_wxf_notation(&self, m: &Move) -> Result<String, ChessError> {
+        self.layout.to_wxf_notation(m)
+    }
+
+    /// Count the positions `depth` plies ahead, to check move generation.
+    pub fn perft(&self, depth: usize) -> usize {
+        self.layout.perft(self.turn, depth)
+    }
+
+    /// `perft` split by the first move.
+    pub fn divide(&self, depth: usize) -> Vec<(Move, usize)> {
+        self.layout.divide(self.turn, depth)
+    }
+
+    /// Take back the last move. Returns false if there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let Some(record) = self.history.pop() else {
+            return false;
+        };
+        self.layout.unmake_move(&record.undo);
+        self.turn = self.turn.opponent();
+        self.undone.push(record);
+        true
+    }
+
+    /// Replay the last undone move. Returns false if there is nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        let Some(record) = self.undone.pop() else {
+            return false;
+        };
+        self.apply(&record.undo.m);
+        true
+    }
+
+    #[cfg(test)]
+    fn test_run(&mut self, scripts: &[&str]) {
+        for input in scripts {
+            let m = self
+                .input
+                .parse_input(input, &self.layout, self.turn)
+                .unwrap_or_else(|e| panic!("cannot handle {}: {}", input, e));
+            println!("handling {}, ucci: {}", input, m);
+            self.play(&m)
+                .unwrap_or_else(|e| panic!("cannot play {}: {}", input, e));
+        }
+        println!("{}", self.layout);
+    }
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Draws the board.
+impl fmt::Display for Game {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chessman::PieceKind, search::Limits};
+    use std::time::Duration;
+
+    #[test]
+    fn test_classic_interpolate() {
+        let input = [
+            "炮二平五",
+            "炮8平5",
+            "炮五进四",
+            "士4进5",
+            "马二进三",
+            "马8进7",
+            "炮八平五",
+            "马2进3",
+            "前炮退二",
+            "车9平8",
+        ];
+        let mut game = Game::new();
+        game.test_run(&input);
+        let fen = game.layout.to_fen_string();
+        assert_eq!(
+            fen,
+            "r1b1kabr1/4a4/1cn1c1n2/p1p3p1p/9/4C4/P1P1P1P1P/4C1N2/9/RNBAKAB1R"
+        );
+    }
+
+    #[test]
+    fn test_perft() {
+        // published perft numbers, checked to depth 3 to keep the suite fast
+        let cases: [(&str, [usize; 3]); 6] = [
+            (START_FEN, [44, 1920, 79666]),
+            (
+                "r1ba1a3/4kn3/2n1b4/pNp1p1p1p/4c4/6P2/P1P2R2P/1CcC5/9/2BAKAB2 w - - 0 1",
+                [38, 1128, 43929],
+            ),
+            (
+                "1cbak4/9/n2a5/2p1p3p/5cp2/2n2N3/6PCP/3AB4/2C6/3A1K1N1 w - - 0 1",
+                [7, 281, 8620],
+            ),
+            (
+                "5a3/3k5/3aR4/9/5r3/5n3/9/3A1A3/5K3/2BC2B2 w - - 0 1",
+                [25, 424, 9850],
+            ),
+            (
+                "CRN1k1b2/3ca4/4ba3/9/2nr5/9/9/4B4/4A4/4KA3 w - - 0 1",
+                [28, 516, 14808],
+            ),
+            (
+                "R1N1k1b2/9/3aba3/9/2nr5/2B6/9/4B4/4A4/4KA3 w - - 0 1",
+                [21, 364, 7626],
+            ),
+        ];
+        let mut game = Game::new();
+        for (fen, counts) in cases {
+            game.parse_fen(fen).unwrap();
+            for (depth, count) in counts.into_iter().enumerate() {
+                assert_eq!(
+                    game.layout.perft(game.turn, depth + 1),
+                    count,
+                    "depth {} of {}",
+                    depth + 1,
+                    fen
+                );
+            }
+            let divide = game.layout.divide(game.turn, 2);
+            assert_eq!(divide.iter().map(|(_, n)| n).sum::<usize>(), counts[1]);
+        }
+    }
+
+    #[test]
+    fn test_legal_moves() {
+        let mut game = Game::new();
+        assert_eq!(game.layout.legal_moves(Color::Red).len(), 44);
+        assert_eq!(game.layout.legal_moves(Color::Black).len(), 44);
+
+        // the horse on b0 is blocked by the piece on b1, the cannon on e2
+        // can only capture the black rook over the red pawn screen
+        game.parse_fen("3kr4/9/9/9/9/9/4P4/4C4/1R7/1N2K4 w - - 0 1")
+            .unwrap();
+        let moves = game.layout.legal_moves(Color::Red);
+        assert!(!moves.contains(&Move::from_iccs("b0a2").unwrap()));
+        assert!(!moves.contains(&Move::from_iccs("b0c2").unwrap()));
+        assert!(moves.contains(&Move::from_iccs("b0d1").unwrap()));
+        let cannon_up: Vec<_> = moves
+            .iter()
+            .filter(|m| m.from == Square::from_iccs("e2").unwrap() && m.to.rank().index() > 2)
+            .collect();
+        assert_eq!(cannon_up.len(), 1);
+        assert_eq!(cannon_up[0].to, Square::from_iccs("e9").unwrap());
+    }
+
+    #[test]
+    fn test_check_and_mate() {
+        let mut game = Game::new();
+        assert!(!game.layout.is_in_check(Color::Red));
+        assert_eq!(game.game_result(), None);
+
+        game.parse_fen("R3k4/R8/9/9/9/9/9/9/9/3K5 b - - 0 1")
+            .unwrap();
+        assert!(game.layout.is_in_check(Color::Black));
+        assert_eq!(
+            game.game_result(),
+            Some(GameResult::Checkmate { winner: Color::Red })
+        );
+
+        game.parse_fen("3k5/8R/9/9/9/9/9/9/4R4/5K3 b - - 0 1")
+            .unwrap();
+        assert!(!game.layout.is_in_check(Color::Black));
+        assert_eq!(
+            game.game_result(),
+            Some(GameResult::Stalemate { winner: Color::Red })
+        );
+
+        // the advisor is pinned by the rook and cannot leave the file
+        game.parse_fen("4k4/4a4/9/9/9/9/9/9/9/3KR4 b - - 0 1")
+            .unwrap();
+        let m = Move::from_iccs("e8d9").unwrap();
+        assert_eq!(game.layout.is_valid_move(&m), Ok(false));
+    }
+
+    #[test]
+    fn test_flying_general() {
+        let mut game = Game::new();
+        game.parse_fen("3k5/9/9/9/9/9/9/9/4A4/4K4 w - - 0 1")
+            .unwrap();
+        assert!(!game.layout.is_in_check(Color::Red));
+        // the king may not step onto the open file of the other king
+        let m = Move::from_iccs("e0d0").unwrap();
+        assert_eq!(game.layout.is_valid_move(&m), Ok(false));
+
+        game.parse_fen("4k4/9/9/9/9/9/9/9/4A4/4K4 w - - 0 1")
+            .unwrap();
+        // the advisor is the only chessman between the generals
+        let m = Move::from_iccs("e1d2").unwrap();
+        assert_eq!(game.layout.is_valid_move(&m), Ok(false));
+
+        // stepping aside anyway leaves both generals in check
+        game.layout.make_move(&m).unwrap();
+        assert!(game.layout.is_in_check(Color::Red));
+        assert!(game.layout.is_in_check(Color::Black));
+    }
+
+    #[test]
+    fn test_turn() {
+        let mut game = Game::new();
+        game.test_run(&["炮二平五", "炮8平5", "炮五进四"]);
+        assert_eq!(game.turn, Color::Black);
+        assert_eq!(game.round(), 2);
+        assert_eq!(game.peace_round(), 0);
+
+        // red cannot move twice in a row
+        let m = Move::from_iccs("b2b9").unwrap();
+        assert_eq!(game.play(&m), Err(ChessError::WrongSide(Color::Black)));
+        assert_eq!(game.turn, Color::Black);
+
+        // the rook cannot jump the pawn, and the cannon on e6 checks black
+        // over its own cannon, so a pawn move is no answer either
+        let fen = game.to_fen_string();
+        for m in ["a9a5", "a6a5"] {
+            let m = Move::from_iccs(m).unwrap();
+            assert_eq!(game.play(&m), Err(ChessError::IllegalMove(m)));
+            assert_eq!(game.to_fen_string(), fen);
+        }
+
+        game.test_run(&["士4进5", "马二进三"]);
+        assert_eq!(game.turn, Color::Black);
+        assert_eq!(game.round(), 3);
+        assert_eq!(game.peace_round(), 2);
+    }
+
+    #[test]
+    fn test_fen_round_trip() {
+        let mut game = Game::new();
+        assert_eq!(
+            game.to_fen_string(),
+            "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1"
+        );
+        game.test_run(&["炮二平五", "炮8平5", "炮五进四", "士4进5", "马二进三"]);
+        let fen = game.to_fen_string();
+        assert_eq!(
+            fen,
+            "rnb1kabnr/4a4/1c2c4/p1p1C1p1p/9/9/P1P1P1P1P/1C4N2/9/RNBAKAB1R b - - 2 3"
+        );
+
+        let mut other = Game::new();
+        other.parse_fen(&fen).unwrap();
+        assert_eq!(other.to_fen_string(), fen);
+    }
+
+    #[test]
+    fn test_bad_fen() {
+        let mut game = Game::new();
+        let cases = [
+            ("rnbakabnr/9/1c5c1 w - - 0 1", FenError::WrongRankCount(3)),
+            (
+                "rnbakabnx/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1",
+                FenError::BadPiece('x'),
+            ),
+            (
+                "rnbakabnr/9/1c5c1/p1p1p1p1p/91/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1",
+                FenError::RankTooLong(4),
+            ),
+            (
+                "rnbakabnr/9/1c5c1/p1p1p1p1p/8/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1",
+                FenError::RankTooShort(4),
+            ),
+            (
+                "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR",
+                FenError::MissingSide,
+            ),
+            (
+                "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR x - - 0 1",
+                FenError::BadSide("x".to_string()),
+            ),
+            (
+                "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - a 1",
+                FenError::BadCounter("a".to_string()),
+            ),
+            (
+                "rnba1abnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1",
+                FenError::MissingKing(Color::Black),
+            ),
+            (
+                "rnbakabnr/9/1c5c1/p1p1p1p1p/9/4R4/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1",
+                FenError::TooManyPieces(Chessman::new(PieceKind::Rook, Color::Red)),
+            ),
+            (
+                "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/4B4/RNBAKA1NR w - - 0 1",
+                FenError::IllegalSquare(
+                    Chessman::new(PieceKind::Elephant, Color::Red),
+                    Square::from_iccs("e1").unwrap(),
+                ),
+            ),
+            (
+                "4k4/9/9/9/9/9/9/9/9/4K4 w - - 0 1",
+                FenError::OpponentInCheck(Color::Black),
+            ),
+            (
+                "4k4/9/9/9/9/9/9/9/3r5/3K5 b - - 0 1",
+                FenError::OpponentInCheck(Color::Red),
+            ),
+        ];
+        for (fen, err) in cases {
+            assert_eq!(game.parse_fen(fen), Err(err.into()));
+        }
+        // a rejected FEN leaves the game untouched
+        assert_eq!(
+            game.to_fen_string(),
+            "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1"
+        );
+
+        // the optional fields are recognised by their content
+        game.parse_fen("rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR b 3 7")
+            .unwrap();
+        assert_eq!((game.peace_round(), game.round()), (3, 7));
+        game.parse_fen("rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w")
+            .unwrap();
+        assert_eq!((game.peace_round(), game.round()), (0, 1));
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut game = Game::new();
+        assert!(!game.undo());
+        game.test_run(&["炮二平五", "炮8平5", "炮五进四"]);
+        let fen = game.to_fen_string();
+        game.test_run(&["士4进5"]);
+
+        assert!(game.undo());
+        assert_eq!(game.to_fen_string(), fen);
+        // the captured pawn comes back
+        assert!(game.undo());
+        assert!(game.undo());
+        assert!(game.undo());
+        assert!(!game.undo());
+        assert_eq!(
+            game.to_fen_string(),
+            "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1"
+        );
+
+        for _ in 0..3 {
+            assert!(game.redo());
+        }
+        assert_eq!(game.to_fen_string(), fen);
+
+        // a new move drops the moves left to redo
+        game.test_run(&["士6进5"]);
+        assert!(!game.redo());
+    }
+
+    #[test]
+    fn test_chinese_notation() {
+        let mut game = Game::new();
+        let m = Move::from_iccs("h2e2").unwrap();
+        assert_eq!(game.layout.to_chinese_notation(&m).unwrap(), "炮二平五");
+        let m = Move::from_iccs("h9g7").unwrap();
+        assert_eq!(game.layout.to_chinese_notation(&m).unwrap(), "马8进7");
+
+        let fens = [
+            "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1",
+            "r1b1kabr1/4a4/1cn1c1n2/p1p3p1p/9/4C4/P1P1P1P1P/4C1N2/9/RNBAKAB1R w - - 0 1",
+            // two columns of red pawns and three black pawns on one column
+            "3k5/2P3P2/2P3P2/9/4p4/4p4/4p4/9/4A4/3AK4 w - - 0 1",
+            // four red pawns on one column
+            "3k5/4P4/4P4/4P4/4P4/9/9/9/9/4K4 w - - 0 1",
+        ];
+        for fen in fens {
+            game.parse_fen(fen).unwrap();
+            for turn in [Color::Red, Color::Black] {
+                for m in game.layout.legal_moves(turn) {
+                    let notation = game.layout.to_chinese_notation(&m).unwrap();
+                    let parsed = game.input.parse_input(&notation, &game.layout, turn);
+                    assert_eq!(parsed, Ok(m), "{} in {}", notation, fen);
+                }
+            }
+        }
+
+        game.parse_fen("3k5/2P3P2/2P3P2/9/4p4/4p4/4p4/9/4A4/3AK4 w - - 0 1")
+            .unwrap();
+        let m = Move::from_iccs("c8c9").unwrap();
+        assert_eq!(game.layout.to_chinese_notation(&m).unwrap(), "前七进一");
+        let m = Move::from_iccs("e3e2").unwrap();
+        assert_eq!(game.layout.to_chinese_notation(&m).unwrap(), "前卒进1");
+    }
+
+    #[test]
+    fn test_iccs_input() {
+        let mut game = Game::new();
+        game.input.set_mode(input::InputMode::Iccs);
+        game.test_run(&["h2e2", "h9-g7", "E2-E6"]);
+        assert_eq!(
+            game.to_fen_string(),
+            "rnbakab1r/9/1c4nc1/p1p1C1p1p/9/9/P1P1P1P1P/1C7/9/RNBAKABNR b - - 0 2"
+        );
+        // empty square, blocked cannon, off the board, misplaced dashes
+        let square = |s| Square::from_iccs(s).unwrap();
+        let cases = [
+            ("e5e4", ChessError::EmptySquare(square("e5"))),
+            (
+                "b7b1",
+                ChessError::IllegalMove(Move::from_iccs("b7b1").unwrap()),
+            ),
+            ("j0j1", ChessError::BadCoordinates("j0j1".to_string())),
+        ];
+        for (input, err) in cases {
+            let parsed = game.input.parse_input(input, &game.layout, game.turn);
+            assert_eq!(parsed, Err(err));
+        }
+        for input in ["b-2b1", "b2--b1", "-b2b1"] {
+            let parsed = game.input.parse_input(input, &game.layout, game.turn);
+            assert_eq!(parsed, Err(ChessError::BadCoordinates(input.to_string())));
+        }
+    }
+
+    #[test]
+    fn test_parse_command() {
+        let game = Game::new();
+        let cases = [
+            ("  undo ", Ok(Command::Undo)),
+            ("perft 3", Ok(Command::Perft(3))),
+            ("divide x", Err(ChessError::BadDepth("x".to_string()))),
+            ("mode wxf", Ok(Command::Mode(InputMode::Wxf))),
+            ("mode x", Err(ChessError::BadInputMode("x".to_string()))),
+            (
+                "炮二平五",
+                Ok(Command::Move(Move::from_iccs("h2e2").unwrap())),
+            ),
+        ];
+        for (line, command) in cases {
+            assert_eq!(game.parse_command(line), command, "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_wxf_notation() {
+        let mut game = Game::new();
+        game.input.set_mode(input::InputMode::Wxf);
+        game.test_run(&["C2=5", "h8+7", "H2+3", "R9.8"]);
+        assert_eq!(
+            game.to_fen_string(),
+            "rnbakabr1/9/1c4nc1/p1p1p1p1p/9/9/P1P1P1P1P/1C2C1N2/9/RNBAKAB1R w - - 4 3"
+        );
+        game.test_run(&["C5+4", "H2+3", "C8=5", "P3+1", "+C-2"]);
+
+        let fens = [
+            "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1",
+            "r1b1kabr1/4a4/1cn1c1n2/p1p3p1p/9/4C4/P1P1P1P1P/4C1N2/9/RNBAKAB1R w - - 0 1",
+            "3k5/2P3P2/2P3P2/9/4p4/4p4/4p4/9/4A4/3AK4 w - - 0 1",
+            "3k5/4P4/4P4/4P4/4P4/9/9/9/9/4K4 w - - 0 1",
+        ];
+        for fen in fens {
+            game.parse_fen(fen).unwrap();
+            for turn in [Color::Red, Color::Black] {
+                for m in game.layout.legal_moves(turn) {
+                    let notation = game.layout.to_wxf_notation(&m).unwrap();
+                    let parsed = game.input.parse_input(&notation, &game.layout, turn);
+                    assert_eq!(parsed, Ok(m), "{} in {}", notation, fen);
+                }
+            }
+        }
+        let m = Move::from_iccs("e2e0").unwrap();
+        game.parse_fen(fens[1]).unwrap();
+        assert_eq!(game.layout.to_wxf_notation(&m).unwrap(), "-C-2");
+    }
+
+    #[test]
+    fn test_ucci() {
+        let mut game = Game::new();
+        let script = "ucci
+isready
+position startpos moves h2e2 h9g7
+go depth 1
+position fen 3k5/9/9/9/9/9/9/9/3R5/3RK4 b - - 0 1
+go time 1000
+quit
+isready
+";
+        let mut output = vec![];
+        ucci::run(&mut game, script.as_bytes(), &mut output);
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines[..5],
+            [
+                "id name chess",
+                "id author An-n-ya",
+                "option hashsize type spin min 1 max 1024 default 16",
+                "ucciok",
+                "readyok"
+            ]
+        );
+        assert!(lines[5].starts_with("info depth 1 "));
+        assert!(lines[6].starts_with("bestmove "));
+        let m = Move::from_iccs(&lines[6]["bestmove ".len()..]).unwrap();
+        game.parse_fen("rnbakab1r/9/1c4nc1/p1p1p1p1p/9/9/P1P1P1P1P/1C2C4/9/RNBAKABNR w - - 2 2")
+            .unwrap();
+        assert_eq!(game.layout.is_valid_move(&m), Ok(true));
+        assert_eq!(lines[7..], ["nobestmove", "bye"]);
+    }
+
+    #[test]
+    fn test_ucci_time() {
+        // five minutes left must not be spent on a single move
+        let limits = ucci::parse_go(&["time", "300000"]);
+        assert_eq!(limits.time, Some(Duration::from_millis(10000)));
+        let limits = ucci::parse_go(&["time", "300000", "movestogo", "10", "increment", "2000"]);
+        assert_eq!(limits.time, Some(Duration::from_millis(31000)));
+        // with the last move before the time control only the margin is kept
+        let limits = ucci::parse_go(&["time", "1000", "movestogo", "1"]);
+        assert_eq!(limits.time, Some(Duration::from_millis(950)));
+        // UCI budgets the same way, even with a large increment
+        let limits = uci::parse_go(
+            &["wtime", "1000", "winc", "4000", "movestogo", "1"],
+            Color::Red,
+        );
+        assert_eq!(limits.time, Some(Duration::from_millis(950)));
+        let limits = uci::parse_go(
+            &["wtime", "1000", "btime", "60000", "movetime", "300"],
+            Color::Black,
+        );
+        assert_eq!(limits.time, Some(Duration::from_millis(300)));
+    }
+
+    #[test]
+    fn test_uci() {
+        let mut game = Game::new();
+        let script = "uci
+setoption name Hash value 4
+setoption name Clear Hash
+setoption name Ponder value true
+isready
+ucinewgame
+position startpos moves h2e2 h9g7
+go wtime 3000 btime 3000 winc 0 binc 0
+position fen 3k5/9/9/9/9/9/9/9/3R5/3RK4 b - - 0 1
+go movetime 100
+quit
+isready
+";
+        let mut output = vec![];
+        uci::run(&mut game, script.as_bytes(), &mut output);
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines[..7],
+            [
+                "id name chess",
+                "id author An-n-ya",
+                "option name Hash type spin default 16 min 1 max 1024",
+                "option name Clear Hash type button",
+                "uciok",
+                "info string unsupported option Ponder",
+                "readyok"
+            ]
+        );
+        let (last, lines) = lines[7..].split_last().unwrap();
+        let (bestmove, infos) = lines.split_last().unwrap();
+        assert!(!infos.is_empty());
+        assert!(infos.iter().all(|l| l.starts_with("info depth ")));
+        assert!(bestmove.starts_with("bestmove "));
+        assert_eq!(*last, "bestmove (none)");
+    }
+
+    #[test]
+    fn test_search() {
+        let mut game = Game::new();
+        // the rooks end the game in one move
+        game.parse_fen("4k4/8R/9/9/9/9/9/9/9/R2K5 w - - 0 1")
+            .unwrap();
+        let limits = Limits {
+            depth: Some(3),
+            ..Default::default()
+        };
+        let result = game.search(&limits);
+        assert_eq!(result.mate_in(), Some(1));
+        assert_eq!(result.score, search::MATE - 1);
+        // a found mate ends the iterative deepening early
+        assert_eq!(result.depth, 1);
+        game.play(&result.best.unwrap()).unwrap();
+        assert_eq!(
+            game.game_result(),
+            Some(GameResult::Checkmate { winner: Color::Red })
+        );
+
+        // take the hanging rook
+        game.parse_fen("4k4/9/9/9/4r4/9/9/4R4/9/3K5 w - - 0 1")
+            .unwrap();
+        let result = game.search(&limits);
+        assert_eq!(result.best, Move::from_iccs("e2e5"));
+        assert!(result.score > 0);
+        assert_eq!(result.pv.first(), result.best.as_ref());
+
+        let limits = Limits {
+            nodes: Some(2000),
+            ..Default::default()
+        };
+        let result = Game::new().search(&limits);
+        assert!(result.best.is_some());
+        assert!(result.nodes < 2000 + 1024);
+    }
+
+    #[test]
+    fn test_evaluate() {
+        use evaluate::{evaluate, EvalConfig};
+
+        let config = EvalConfig::default();
+        let mut game = Game::new();
+        // the start position is symmetric
+        assert_eq!(evaluate(&game.layout, Color::Red, &config), 0);
+        assert_eq!(evaluate(&game.layout, Color::Black, &config), 0);
+
+        // a central cannon is worth more than a cannon on the flank
+        game.test_run(&["炮二平五"]);
+        let score = evaluate(&game.layout, Color::Red, &config);
+        assert!(score > 0);
+        assert_eq!(evaluate(&game.layout, Color::Black, &config), -score);
+
+        // a crossed pawn is worth more than one at home
+        game.parse_fen("4k4/9/9/4P4/9/9/9/9/9/3K5 w - - 0 1")
+            .unwrap();
+        let crossed = evaluate(&game.layout, Color::Red, &config);
+        game.parse_fen("4k4/9/9/9/9/9/4P4/9/9/3K5 w - - 0 1")
+            .unwrap();
+        let home = evaluate(&game.layout, Color::Red, &config);
+        assert!(crossed > home + config.crossed_pawn);
+
+        // each term can be switched off
+        let plain = EvalConfig {
+            crossed_pawn: 0,
+            ..config
+        };
+        game.parse_fen("4k4/9/9/4P4/9/9/9/9/9/3K5 w - - 0 1")
+            .unwrap();
+        assert_eq!(
+            evaluate(&game.layout, Color::Red, &plain),
+            crossed - config.crossed_pawn
+        );
+
+        // the engine searches with the weights set on the game
+        game.parse_fen("4k4/9/2n6/9/9/2R3p2/9/9/9/3K5 w - - 0 1")
+            .unwrap();
+        let limits = Limits {
+            depth: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(game.search(&limits).best, Move::from_iccs("c4c7"));
+        game.set_eval_config(EvalConfig {
+            pawn: 2 * config.horse,
+            ..config
+        });
+        assert_eq!(game.search(&limits).best, Move::from_iccs("c4g4"));
+    }
+
+    #[test]
+    fn test_zobrist() {
+        let mut game = Game::new();
+        let start = game.layout.hash();
+        game.test_run(&["炮二平五", "马8进7", "马八进七"]);
+        let hash = game.layout.hash();
+        let mut fresh = game.layout.clone();
+        fresh.reset_hash(game.turn);
+        assert_eq!(hash, fresh.hash());
+
+        // the same position reached by another move order
+        let mut other = Game::new();
+        other.test_run(&["马八进七", "马8进7", "炮二平五"]);
+        assert_eq!(other.layout.hash(), hash);
+
+        // the side to move is part of the key
+        fresh.reset_hash(game.turn.opponent());
+        assert_ne!(fresh.hash(), hash);
+
+        while game.undo() {}
+        assert_eq!(game.layout.hash(), start);
+    }
+
+    #[test]
+    fn test_transposition_table() {
+        use tt::{Bound, TranspositionTable};
+
+        let mut tt = TranspositionTable::new(1);
+        let m = Move::from_iccs("h2e2");
+        tt.store(42, 3, 0, Bound::Exact, 15, m);
+        let e = tt.probe(42).unwrap();
+        assert_eq!(
+            (e.depth, e.bound, e.score(0), e.best),
+            (3, Bound::Exact, 15, m)
+        );
+        assert!(tt.probe(43).is_none());
+
+        // a mate found 2 plies below a node at ply 3 is mate in 2 from any node
+        tt.store(7, 2, 3, Bound::Exact, search::MATE - 5, None);
+        assert_eq!(tt.probe(7).unwrap().score(1), search::MATE - 3);
+        tt.clear();
+        assert!(tt.probe(42).is_none());
+
+        // the table doesn't change what a search finds
+        let game = Game::new();
+        let limits = Limits {
+            depth: Some(3),
+            ..Default::default()
+        };
+        let mut tt = TranspositionTable::new(1);
+        let first = search::search_with(
+            &game.layout,
+            Color::Red,
+            &limits,
+            &game.eval_config,
+            &mut tt,
+            |_| {},
+        );
+        let second = search::search_with(
+            &game.layout,
+            Color::Red,
+            &limits,
+            &game.eval_config,
+            &mut tt,
+            |_| {},
+        );
+        assert_eq!(first.score, second.score);
+        assert!(second.nodes < first.nodes);
+
+        // hash sizes out of the advertised range are clamped, not allocated
+        assert_eq!(
+            TranspositionTable::entry_count(usize::MAX),
+            TranspositionTable::entry_count(tt::MAX_SIZE_MB)
+        );
+        assert_eq!(
+            TranspositionTable::entry_count(10_000_000),
+            TranspositionTable::entry_count(tt::MAX_SIZE_MB)
+        );
+        assert_eq!(
+            TranspositionTable::entry_count(0),
+            TranspositionTable::entry_count(1)
+        );
+        tt.resize(0);
+        assert!(tt.probe(42).is_none());
+    }
+
+    #[test]
+    fn test_see_and_quiescence() {
+        use evaluate::EvalConfig;
+
+        let config = EvalConfig::default();
+        let mut game = Game::new();
+        let m = Move::from_iccs("a5e5").unwrap();
+        // the rook takes the horse, the cannon takes the rook over the elephant,
+        // then the red cannon takes back over the red pawn
+        game.parse_fen("3k5/4c4/4b4/9/R3n4/9/4P4/9/9/4CK3 w - - 0 1")
+            .unwrap();
+        assert_eq!(
+            game.layout.see(&m, &config).unwrap(),
+            config.horse - config.rook + config.cannon
+        );
+        // without its screen the red cannon cannot take back
+        game.parse_fen("3k5/4c4/4b4/9/R3n4/9/9/9/9/4CK3 w - - 0 1")
+            .unwrap();
+        assert_eq!(
+            game.layout.see(&m, &config).unwrap(),
+            config.horse - config.rook
+        );
+        // and without the elephant the horse is hanging
+        game.parse_fen("3k5/4c4/9/9/R3n4/9/9/9/9/4CK3 w - - 0 1")
+            .unwrap();
+        assert_eq!(game.layout.see(&m, &config).unwrap(), config.horse);
+
+        // a one ply search no longer grabs the defended horse
+        game.parse_fen("3k5/4c4/4b4/9/R3n4/9/9/9/9/4CK3 w - - 0 1")
+            .unwrap();
+        let limits = Limits {
+            depth: Some(1),
+            ..Default::default()
+        };
+        let result = game.search(&limits);
+        assert_ne!(result.best, Some(m));
+    }
+
+    #[test]
+    fn test_perpetual() {
+        let mut game = Game::new();
+        game.input.set_mode(input::InputMode::Iccs);
+        // the rook checks on every move
+        game.parse_fen("4k4/R8/9/9/9/9/9/9/9/3K5 w - - 0 1")
+            .unwrap();
+        game.test_run(&["a8a9", "e9e8", "a9a8", "e8e9", "a8a9", "e9e8", "a9a8"]);
+        assert_eq!(game.game_result(), None);
+        game.test_run(&["e8e9"]);
+        assert_eq!(
+            game.game_result(),
+            Some(GameResult::PerpetualCheck {
+                winner: Color::Black
+            })
+        );
+
+        // the rook keeps chasing the unprotected cannon
+        let fen = "4k4/9/9/8R/c8/9/9/9/9/3K5 w - - 0 1";
+        let moves = [
+            "i6i5", "a5a6", "i5i6", "a6a5", "i6i5", "a5a6", "i5i6", "a6a5",
+        ];
+        game.parse_fen(fen).unwrap();
+        game.test_run(&moves);
+        assert_eq!(
+            game.game_result(),
+            Some(GameResult::PerpetualChase {
+                winner: Color::Black
+            })
+        );
+        game.parse_fen(fen).unwrap();
+        game.set_repetition_rule(RepetitionRule::Chinese);
+        game.test_run(&moves);
+        assert_eq!(game.game_result(), Some(GameResult::Repetition));
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut game = Game::new();
+        game.input.set_mode(input::InputMode::Iccs);
+        game.parse_fen("4k4/9/9/9/9/9/9/9/9/3K5 w - - 0 1").unwrap();
+        assert_eq!(game.game_result(), Some(GameResult::InsufficientMaterial));
+        game.parse_fen("3aka3/9/4b4/9/9/9/9/4B4/4A4/3K5 w - - 0 1")
+            .unwrap();
+        assert_eq!(game.game_result(), Some(GameResult::InsufficientMaterial));
+
+        game.parse_fen("4k4/9/9/9/9/9/9/9/9/R2K5 w - - 118 60")
+            .unwrap();
+        game.test_run(&["a0a1"]);
+        assert_eq!(game.game_result(), None);
+        game.test_run(&["e9e8"]);
+        assert_eq!(game.game_result(), Some(GameResult::MoveLimit));
+
+        game.parse_fen("4k4/9/9/9/9/9/9/9/9/R2K5 w - - 8 5")
+            .unwrap();
+        game.set_move_limit(10);
+        game.test_run(&["a0a1"]);
+        assert_eq!(game.game_result(), None);
+        game.test_run(&["e9e8"]);
+        assert_eq!(game.game_result(), Some(GameResult::MoveLimit));
+    }
+
+    #[test]
+    fn test_bitboard() {
+        // compare against the square by square rules one ply into a few
+        // crowded positions
+        let fens = [
+            START_FEN,
+            "r1ba1a3/4kn3/2n1b4/pNp1p1p1p/4c4/6P2/P1P2R2P/1CcC5/9/2BAKAB2 w - - 0 1",
+            "1cbak4/9/n2a5/2p1p3p/5cp2/2n2N3/6PCP/3AB4/2C6/3A1K1N1 w - - 0 1",
+            "CRN1k1b2/3ca4/4ba3/9/2nr5/9/9/4B4/4A4/4KA3 w - - 0 1",
+        ];
+        let mut game = Game::new();
+        for fen in fens {
+            game.parse_fen(fen).unwrap();
+            let mut layouts = vec![game.layout.clone()];
+            for m in game.layout.legal_moves(game.turn) {
+                let mut layout = game.layout.clone();
+                layout.make_move(&m).unwrap();
+                layouts.push(layout);
+            }
+            for layout in layouts {
+                for side in [Color::Red, Color::Black] {
+                    let sorted = |mut moves: Vec<Move>| {
+                        moves.sort_by_key(|m| (m.from, m.to));
+                        moves
+                    };
+                    assert_eq!(
+                        sorted(layout.legal_moves(side)),
+                        sorted(layout.legal_moves_by_scan(side)),
+                        "{}",
+                        layout.to_fen_string()
+                    );
+                    for square in Square::all() {
+                        assert_eq!(
+                            layout.is_attacked(square, side),
+                            layout.is_attacked_by_scan(square, side)
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_make_unmake() {
+        let fens = [
+            START_FEN,
+            "r1ba1a3/4kn3/2n1b4/pNp1p1p1p/4c4/6P2/P1P2R2P/1CcC5/9/2BAKAB2 w - - 0 1",
+            "1cbak4/9/n2a5/2p1p3p/5cp2/2n2N3/6PCP/3AB4/2C6/3A1K1N1 w - - 0 1",
+            "5a3/3k5/3aR4/9/5r3/5n3/9/3A1A3/5K3/2BC2B2 w - - 0 1",
+        ];
+        let mut game = Game::new();
+        for fen in fens {
+            game.parse_fen(fen).unwrap();
+            for first in game.layout.legal_moves(game.turn) {
+                let mut layout = game.layout.clone();
+                let outer = layout.make_move(&first).unwrap();
+                let before = layout.clone();
+                for m in layout.legal_moves(game.turn.opponent()) {
+                    let undo = layout.make_move(&m).unwrap();
+                    let mut fresh = layout.clone();
+                    fresh.reset_hash(game.turn);
+                    assert_eq!(layout.hash(), fresh.hash());
+                    let counters = layout.counters();
+                    let black = game.turn == Color::Red;
+                    assert_eq!(counters.round, before.counters().round + black as usize);
+                    if undo.captured.is_some() {
+                        assert_eq!(counters.peace_round, 0);
+                    } else {
+                        assert_eq!(counters.peace_round, before.counters().peace_round + 1);
+                    }
+                    layout.unmake_move(&undo);
+                    assert_eq!(layout.counters(), before.counters());
+                    assert!(layout == before, "{} {}", before.to_fen_string(), m);
+                }
+                layout.unmake_move(&outer);
+                assert_eq!(layout.counters(), game.layout.counters());
+                assert!(layout == game.layout);
+            }
+
+            // the game counters come back with the position
+            let fen = game.to_fen_string();
+            for m in game.layout.legal_moves(game.turn) {
+                game.play(&m).unwrap();
+                assert!(game.undo());
+                assert_eq!(game.to_fen_string(), fen);
+            }
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut game = Game::new();
+        // typos are reported instead of panicking
+        let cases = [
+            ("炮二平X", ChessError::BadNumber('X')),
+            ("炮X平五", ChessError::BadNumber('X')),
+            ("X二平五", ChessError::BadChessman('X')),
+            ("炮二平0", ChessError::BadNumber('0')),
+            ("炮0平五", ChessError::BadNumber('0')),
+            ("炮二上五", ChessError::BadDirection('上')),
+            ("炮二平", ChessError::BadLength(3)),
+            ("车二进一", ChessError::NoSuchChessman("车二".to_string())),
+            ("炮二进九", ChessError::TooFar),
+            (
+                "车一进四",
+                ChessError::IllegalMove(Move::from_iccs("i0i4").unwrap()),
+            ),
+        ];
+        for (typo, err) in cases {
+            assert_eq!(game.parse_move(typo), Err(err), "{}", typo);
+        }
+        game.set_input_mode(InputMode::Wxf);
+        assert_eq!(game.parse_move("X2=5"), Err(ChessError::BadChessman('X')));
+        assert_eq!(game.parse_move("C2*5"), Err(ChessError::BadDirection('*')));
+
+        assert_eq!(Chessman::try_from('x'), Err(ChessError::BadChessman('x')));
+        assert_eq!(
+            Chessman::try_from('炮'),
+            Ok(Chessman::new(PieceKind::Cannon, Color::Red))
+        );
+        assert_eq!(
+            Chessman::try_from('h'),
+            Ok(Chessman::new(PieceKind::Horse, Color::Black))
+        );
+        let empty = Move::from_iccs("e5e4").unwrap();
+        assert_eq!(
+            game.layout.is_valid_move(&empty),
+            Err(ChessError::EmptySquare(Square::from_iccs("e5").unwrap()))
+        );
+        let e5 = Err(ChessError::EmptySquare(Square::from_iccs("e5").unwrap()));
+        assert_eq!(game.layout.to_chinese_notation(&empty), e5);
+        assert_eq!(game.layout.to_wxf_notation(&empty), e5);
+        assert_eq!(
+            game.layout.see(&empty, &evaluate::EvalConfig::default()),
+            Err(ChessError::EmptySquare(Square::from_iccs("e5").unwrap()))
+        );
+        let mut layout = game.layout.clone();
+        assert_eq!(
+            layout.handle_move(&empty),
+            Err(ChessError::EmptySquare(Square::from_iccs("e5").unwrap()))
+        );
+        assert_eq!(
+            layout.make_move(&empty).unwrap_err(),
+            ChessError::EmptySquare(Square::from_iccs("e5").unwrap())
+        );
+        assert!(layout == game.layout);
+        assert_eq!(
+            layout.handle_move(&Move::from_iccs("h2e2").unwrap()),
+            Ok(())
+        );
+        assert_eq!(
+            ChessError::from(FenError::MissingSide).to_string(),
+            "missing side to move"
+        );
+    }
+
+    #[test]
+    fn test_square() {
+        use square::{File, Rank};
+
+        let h2 = Square::from_iccs("h2").unwrap();
+        assert_eq!(h2.file(), File::from_char('h').unwrap());
+        assert_eq!(h2.rank(), Rank::new(2).unwrap());
+        assert_eq!(h2.to_string(), "h2");
+        assert_eq!(Square::new(h2.file(), h2.rank()), h2);
+        assert_eq!(Square::from_iccs("a9").unwrap().index(), 0);
+        assert_eq!(Square::from_iccs("i0").unwrap().index(), 89);
+        assert_eq!(Square::from_iccs("j0"), None);
+        assert_eq!(h2.offset(-3, 0), Square::from_iccs("e2"));
+        assert_eq!(h2.offset(0, -3), None);
+
+        // file h is the 二 of red and the 8 of black
+        assert_eq!(h2.file().number(Color::Red), 2);
+        assert_eq!(h2.file().number(Color::Black), 8);
+        assert_eq!(File::from_number(2, Color::Red), Some(h2.file()));
+        assert_eq!(File::from_number(8, Color::Black), Some(h2.file()));
+        assert_eq!(File::from_number(10, Color::Red), None);
+
+        let e1 = Square::from_iccs("e1").unwrap();
+        assert!(e1.is_in_palace(Color::Red) && !e1.is_in_palace(Color::Black));
+        assert!(e1.is_own_half(Color::Red) && !e1.is_own_half(Color::Black));
+
+        let horse = Chessman::new(PieceKind::Horse, Color::Black);
+        assert_eq!(horse.kind(), PieceKind::Horse);
+        assert_eq!(horse.color(), Color::Black);
+        assert_eq!(Chessman::from_fen_char('n'), Some(horse));
+        assert_eq!(horse.to_fen_char(), 'n');
+        // every chessman has its own bitboard and zobrist keys
+        let mut indices: Vec<usize> = [Color::Red, Color::Black]
+            .iter()
+            .flat_map(|color| PieceKind::ALL.map(|kind| Chessman::new(kind, *color).index()))
+            .collect();
+        indices.sort();
+        assert_eq!(indices, (0..Chessman::COUNT).collect::<Vec<_>>());
+    }
+}
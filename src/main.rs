@@ -1,126 +1,107 @@
-mod chessman;
-mod constants;
-mod input;
-mod layout;
+use std::io::{self, BufRead, Read};
 
-use input::Input;
-use layout::Layout;
-struct Chess {
-    round: usize,
-    peace_round: usize,
-    turn: Turn,
-    layout: Layout,
-    input: Input,
-}
-
-enum Turn {
-    Red,
-    Black,
-}
+use chess::{run_ucci, run_uci, Command, Game, Limits};
 
-impl Chess {
-    pub fn new() -> Self {
-        let mut chess = Self {
-            round: 0,
-            peace_round: 0,
-            turn: Turn::Red,
-            layout: Layout::new(),
-            input: Input::new(),
-        };
-        chess.parse_fen("rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1");
-        chess
+// wait for a line that parses, the end of stdin quits
+fn read_command(game: &Game) -> Command {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if io::stdin().lock().read_line(&mut line).unwrap() == 0 {
+            return Command::Quit;
+        }
+        match game.parse_command(&line) {
+            Ok(command) => return command,
+            Err(e) => eprintln!("{}", e),
+        }
     }
+}
 
-    pub fn parse_fen(&mut self, input: &str) {
-        let mut line_cnt = 0;
-        let lines = input.split('/');
-        let last_line = lines.clone().last().unwrap();
-        for line in lines {
-            let mut column_cnt = 0;
-            for c in line.chars() {
-                if c.is_numeric() {
-                    let n = c as u8 - 0x30;
-                    for _ in 0..n {
-                        self.layout.board[line_cnt][column_cnt] = None;
-                        column_cnt += 1;
+fn run(game: &mut Game) {
+    loop {
+        match read_command(game) {
+            Command::Move(m) => {
+                if let Err(e) = game.play(&m) {
+                    eprintln!("{}", e);
+                    continue;
+                }
+            }
+            Command::Undo => {
+                if !game.undo() {
+                    eprintln!("nothing to undo");
+                    continue;
+                }
+            }
+            Command::Redo => {
+                if !game.redo() {
+                    eprintln!("nothing to redo");
+                    continue;
+                }
+            }
+            Command::Go => {
+                let result = game.search(&Limits::default());
+                let Some(m) = result.best else {
+                    continue;
+                };
+                match game.to_chinese_notation(&m) {
+                    Ok(notation) => println!("{}", notation),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        continue;
                     }
-                } else {
-                    self.layout.board[line_cnt][column_cnt] = Some(c.into());
-                    column_cnt += 1;
                 }
-                if column_cnt == 9 {
-                    break;
+                if let Err(e) = game.play(&m) {
+                    eprintln!("{}", e);
+                    continue;
                 }
             }
-            line_cnt += 1;
-        }
-
-        let commands: Vec<&str> = last_line.split(' ').into_iter().skip(1).collect();
-
-        if commands[0] == "w" || commands[0] == "r" {
-            self.turn = Turn::Red;
-        } else if commands[0] == "b" {
-            self.turn = Turn::Black;
-        }
-
-        self.peace_round = usize::from_str_radix(commands[3], 10).unwrap();
-        self.round = usize::from_str_radix(commands[4], 10).unwrap();
-    }
-
-    pub fn render(&self) {
-        println!("{}", self.layout);
-    }
-
-    pub fn run(&mut self) {
-        loop {
-            let m = self.input.get_move(&self.layout);
-            self.layout.handle_move(&m);
-            self.render();
+            Command::Fen(fen) => {
+                if let Err(e) = game.parse_fen(&fen) {
+                    eprintln!("{}", e);
+                    continue;
+                }
+            }
+            Command::Perft(depth) => {
+                println!("{}", game.perft(depth));
+                continue;
+            }
+            Command::Divide(depth) => {
+                let divide = game.divide(depth);
+                for (m, count) in &divide {
+                    println!("{}: {}", m, count);
+                }
+                let total: usize = divide.iter().map(|(_, n)| n).sum();
+                println!("total: {}", total);
+                continue;
+            }
+            Command::Mode(mode) => {
+                game.set_input_mode(mode);
+                println!("switch to {:?} input", mode);
+                continue;
+            }
+            Command::Ucci => {
+                // answer the `ucci` we just consumed before reading on
+                let input = io::BufReader::new("ucci\n".as_bytes().chain(io::stdin()));
+                run_ucci(game, input, &mut io::stdout());
+                break;
+            }
+            Command::Uci => {
+                let input = io::BufReader::new("uci\n".as_bytes().chain(io::stdin()));
+                run_uci(game, input, &mut io::stdout());
+                break;
+            }
+            Command::Quit => break,
         }
-    }
-
-    fn test_run(&mut self, scripts: &[&str]) {
-        for input in scripts {
-            let m = self
-                .input
-                .parse_input(input, &self.layout)
-                .expect(&format!("cannot handle {}", input));
-            println!("handling {}, ucci: {}", input, m);
-            self.layout.handle_move(&m);
+        println!("{}", game);
+        if let Some(result) = game.game_result() {
+            println!("{}", result);
+            break;
         }
-        self.render();
     }
 }
-fn main() {
-    let mut chess = Chess::new();
-    chess.render();
-    chess.run();
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
-    #[test]
-    fn test_classic_interpolate() {
-        let input = [
-            "炮二平五",
-            "炮8平5",
-            "炮五进四",
-            "士4进5",
-            "马二进三",
-            "马8进7",
-            "炮八平五",
-            "马2进3",
-            "前炮退二",
-            "车9平8",
-        ];
-        let mut chess = Chess::new();
-        chess.test_run(&input);
-        let fen = chess.layout.to_fen_string();
-        assert_eq!(
-            fen,
-            "r1b1kabr1/4a4/1cn1c1n2/p1p3p1p/9/4C4/P1P1P1P1P/4C1N2/9/RNBAKAB1R"
-        );
-    }
+fn main() {
+    let mut game = Game::new();
+    println!("{}", game);
+    run(&mut game);
 }
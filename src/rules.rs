@@ -0,0 +1,120 @@
+use crate::{input::Move, layout::Layout, square::Square, Color, GameResult};
+
+/// How a move in a repetition cycle is judged.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MoveKind {
+    /// Attacks the enemy king.
+    Check,
+    /// Creates a new threat to win a chessman, see `chased`.
+    Chase,
+    /// Threatens nothing.
+    Idle,
+    /// Threatens only captures the rules allow to repeat.
+    Other,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum RepetitionRule {
+    /// AXF rules: perpetual check and perpetual chase both lose.
+    #[default]
+    Asian,
+    /// A simplified chinese variant where only perpetual check loses and
+    /// any other repetition is a draw.
+    Chinese,
+}
+
+/// The position has to occur this many times before the game is judged.
+pub const REPETITION_COUNT: usize = 3;
+
+/// Plies without a capture before the game is drawn, 60 moves per side.
+pub const DEFAULT_MOVE_LIMIT: usize = 120;
+
+/// Neither side has a chessman that can cross the river, so nobody can ever
+/// give mate.
+pub fn insufficient_material(layout: &Layout) -> bool {
+    Square::all()
+        .filter_map(|square| layout.get(square))
+        .all(|chessman| chessman.is_king() || chessman.is_advisor() || chessman.is_elephant())
+}
+
+/// Judge `m`, which has not been played on `layout` yet.
+pub fn classify(layout: &Layout, m: &Move) -> MoveKind {
+    let mover = layout
+        .get(m.from)
+        .unwrap_or_else(|| panic!("cannot find a chessman at position {:?}", m.from));
+    let side = mover.color();
+    let mut after = layout.clone();
+    after.make_move_unchecked(m);
+    if after.is_in_check(side.opponent()) {
+        return MoveKind::Check;
+    }
+    let before = chased(layout, side);
+    if chased(&after, side).iter().any(|t| !before.contains(t)) {
+        return MoveKind::Chase;
+    }
+    let threatens = after
+        .legal_moves(side)
+        .iter()
+        .any(|m| after.get(m.to).is_some());
+    if threatens {
+        MoveKind::Other
+    } else {
+        MoveKind::Idle
+    }
+}
+
+/// Squares of enemy chessmen `side` threatens in a way that counts as
+/// chasing: an unprotected chessman, or a rook attacked by a horse or a
+/// cannon. Kings and pawns may chase freely, and pawns that haven't crossed
+/// the river may be chased freely.
+pub fn chased(layout: &Layout, side: Color) -> Vec<Square> {
+    let mut res = vec![];
+    for m in layout.legal_moves(side) {
+        let attacker = layout.get(m.from).unwrap();
+        let Some(target) = layout.get(m.to) else {
+            continue;
+        };
+        if attacker.is_king() || attacker.is_pawn() || target.is_king() {
+            continue;
+        }
+        if target.is_pawn() && !target.is_cross_river(&m.to) {
+            continue;
+        }
+        let rook_attacked = target.is_rook() && (attacker.is_horse() || attacker.is_cannon());
+        let mut after = layout.clone();
+        after.make_move_unchecked(&m);
+        let protected = after.is_attacked(m.to, side.opponent());
+        if (rook_attacked || !protected) && !res.contains(&m.to) {
+            res.push(m.to);
+        }
+    }
+    res
+}
+
+/// Judge a repetition from the moves of one cycle.
+pub fn adjudicate(cycle: &[(Color, MoveKind)], rule: RepetitionRule) -> GameResult {
+    let perpetual = |side: Color| {
+        let mut kinds = cycle.iter().filter(|(s, _)| *s == side).map(|(_, k)| *k);
+        if kinds.clone().all(|k| k == MoveKind::Check) {
+            Some(MoveKind::Check)
+        } else if rule == RepetitionRule::Asian
+            && kinds.all(|k| k == MoveKind::Check || k == MoveKind::Chase)
+        {
+            Some(MoveKind::Chase)
+        } else {
+            None
+        }
+    };
+    let forbidden = |winner: Color, kind: MoveKind| match kind {
+        MoveKind::Check => GameResult::PerpetualCheck { winner },
+        _ => GameResult::PerpetualChase { winner },
+    };
+    match (perpetual(Color::Red), perpetual(Color::Black)) {
+        // checking is the heavier offence when one side checks and the other chases
+        (Some(MoveKind::Check), Some(MoveKind::Chase)) => forbidden(Color::Black, MoveKind::Check),
+        (Some(MoveKind::Chase), Some(MoveKind::Check)) => forbidden(Color::Red, MoveKind::Check),
+        (Some(kind), None) => forbidden(Color::Black, kind),
+        (None, Some(kind)) => forbidden(Color::Red, kind),
+        _ => GameResult::Repetition,
+    }
+}
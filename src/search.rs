@@ -0,0 +1,306 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    evaluate::{evaluate, EvalConfig},
+    input::Move,
+    layout::Layout,
+    tt::{Bound, TranspositionTable},
+    Color,
+};
+
+pub const MATE: i32 = 30000;
+const MAX_DEPTH: usize = 64;
+// scores beyond this are mates, counted in plies from the root
+pub(crate) const MATE_BOUND: i32 = MATE - MAX_DEPTH as i32;
+// used when the caller gives no limit at all
+const DEFAULT_DEPTH: usize = 4;
+/// Milliseconds left on the clock after a move, for the GUI's overhead.
+const SAFETY_MARGIN: u64 = 50;
+
+/// How long the GUI or the player allows the engine to think.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Limits {
+    pub depth: Option<usize>,
+    pub nodes: Option<usize>,
+    pub time: Option<Duration>,
+}
+
+/// What the last completed iteration found.
+#[derive(Clone, Default, Debug)]
+pub struct SearchResult {
+    pub best: Option<Move>,
+    /// Centipawn-like score from the side to move's point of view.
+    pub score: i32,
+    pub depth: usize,
+    pub nodes: usize,
+    pub pv: Vec<Move>,
+}
+
+impl SearchResult {
+    /// Plies until mate, positive when the side to move mates.
+    pub fn mate_in(&self) -> Option<i32> {
+        if self.score.abs() > MATE_BOUND {
+            Some(self.score.signum() * (MATE - self.score.abs()))
+        } else {
+            None
+        }
+    }
+}
+
+/// Think time for one move out of `time` milliseconds left on the clock:
+/// an even share of the moves to go plus half the increment, but never the
+/// whole clock.
+pub(crate) fn clock_budget(time: u64, increment: u64, moves_to_go: u64) -> Duration {
+    let budget = time / moves_to_go.max(1) + increment / 2;
+    Duration::from_millis(budget.min(time.saturating_sub(SAFETY_MARGIN)))
+}
+
+/// Search the best move of `side` with iterative deepening, weighing the
+/// positions with `config`.
+pub fn search(layout: &Layout, side: Color, limits: &Limits, config: &EvalConfig) -> SearchResult {
+    search_with(
+        layout,
+        side,
+        limits,
+        config,
+        &mut TranspositionTable::default(),
+        |_| {},
+    )
+}
+
+/// Like `search`, reusing `tt` across searches and calling `on_iteration`
+/// after every completed depth.
+pub fn search_with<F: FnMut(&SearchResult)>(
+    layout: &Layout,
+    side: Color,
+    limits: &Limits,
+    config: &EvalConfig,
+    tt: &mut TranspositionTable,
+    mut on_iteration: F,
+) -> SearchResult {
+    let max_depth = match limits {
+        Limits {
+            depth: None,
+            nodes: None,
+            time: None,
+        } => DEFAULT_DEPTH,
+        _ => limits.depth.unwrap_or(MAX_DEPTH).min(MAX_DEPTH),
+    };
+    let mut layout = layout.clone();
+    layout.reset_hash(side);
+    let mut searcher = Searcher {
+        layout,
+        tt,
+        limits: *limits,
+        start: Instant::now(),
+        nodes: 0,
+        stopped: false,
+        pv: vec![],
+        config,
+    };
+    let mut result = SearchResult {
+        best: searcher.layout.legal_moves(side).first().copied(),
+        ..Default::default()
+    };
+    if result.best.is_none() {
+        result.score = -MATE;
+        return result;
+    }
+    for depth in 1..=max_depth {
+        let mut pv = vec![];
+        let score = searcher.negamax(side, depth, 0, -MATE - 1, MATE + 1, &mut pv);
+        if searcher.stopped {
+            break;
+        }
+        result = SearchResult {
+            best: pv.first().copied(),
+            score,
+            depth,
+            nodes: searcher.nodes,
+            pv: pv.clone(),
+        };
+        on_iteration(&result);
+        searcher.pv = pv;
+        if result.best.is_none() || result.mate_in().is_some() {
+            break;
+        }
+    }
+    result.nodes = searcher.nodes;
+    result
+}
+
+struct Searcher<'a> {
+    layout: Layout,
+    tt: &'a mut TranspositionTable,
+    limits: Limits,
+    start: Instant,
+    nodes: usize,
+    stopped: bool,
+    // the principal variation of the previous iteration, searched first
+    pv: Vec<Move>,
+    config: &'a EvalConfig,
+}
+
+impl Searcher<'_> {
+    fn negamax(
+        &mut self,
+        side: Color,
+        depth: usize,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+        pv: &mut Vec<Move>,
+    ) -> i32 {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(1024) && self.out_of_budget() {
+            self.stopped = true;
+        }
+        if self.stopped {
+            return 0;
+        }
+        if depth == 0 {
+            return self.quiescence(side, ply, alpha, beta);
+        }
+
+        let key = self.layout.hash();
+        let entry = self.tt.probe(key);
+        if let Some(e) = entry.filter(|e| ply > 0 && e.depth as usize >= depth) {
+            let score = e.score(ply);
+            let cutoff = match e.bound {
+                Bound::Exact => true,
+                Bound::Lower => score >= beta,
+                Bound::Upper => score <= alpha,
+            };
+            if cutoff {
+                pv.clear();
+                pv.extend(e.best);
+                return score;
+            }
+        }
+
+        let mut moves = self.layout.legal_moves(side);
+        if moves.is_empty() {
+            // being mated or stalemated both lose, prefer the longest defence
+            return -MATE + ply as i32;
+        }
+        self.order_moves(&mut moves, ply, entry.and_then(|e| e.best));
+
+        let original_alpha = alpha;
+        let mut best_score = -MATE - 1;
+        let mut best = None;
+        let mut child_pv = vec![];
+        for m in moves {
+            let undo = self.layout.make_move_unchecked(&m);
+            child_pv.clear();
+            let score = -self.negamax(
+                side.opponent(),
+                depth - 1,
+                ply + 1,
+                -beta,
+                -alpha,
+                &mut child_pv,
+            );
+            self.layout.unmake_move(&undo);
+            if self.stopped {
+                return 0;
+            }
+            if score > best_score {
+                best_score = score;
+                best = Some(m);
+            }
+            if score > alpha {
+                alpha = score;
+                pv.clear();
+                pv.push(m);
+                pv.extend_from_slice(&child_pv);
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score <= original_alpha {
+            Bound::Upper
+        } else {
+            Bound::Exact
+        };
+        self.tt.store(key, depth, ply, bound, best_score, best);
+        best_score
+    }
+
+    // only captures are searched past the horizon, so a search doesn't stop
+    // in the middle of an exchange; the side to move may also stand pat
+    fn quiescence(&mut self, side: Color, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(1024) && self.out_of_budget() {
+            self.stopped = true;
+        }
+        if self.stopped {
+            return 0;
+        }
+        let in_check = self.layout.is_in_check(side);
+        let mut best_score = -MATE + ply as i32;
+        if !in_check {
+            best_score = evaluate(&self.layout, side, self.config);
+            if best_score >= beta || ply >= MAX_DEPTH {
+                return best_score;
+            }
+            alpha = alpha.max(best_score);
+        }
+
+        let mut moves = self.layout.legal_moves(side);
+        // every evasion is searched when in check, it may be mate
+        if !in_check {
+            moves.retain(|m| {
+                self.layout.get(m.to).is_some()
+                    && self.layout.see(m, self.config).is_ok_and(|v| v >= 0)
+            });
+        }
+        self.order_moves(&mut moves, ply, None);
+        for m in moves {
+            let undo = self.layout.make_move_unchecked(&m);
+            let score = -self.quiescence(side.opponent(), ply + 1, -beta, -alpha);
+            self.layout.unmake_move(&undo);
+            if self.stopped {
+                return 0;
+            }
+            if score > best_score {
+                best_score = score;
+                if score > alpha {
+                    alpha = score;
+                    if alpha >= beta {
+                        break;
+                    }
+                }
+            }
+        }
+        best_score
+    }
+
+    // previous principal variation and the table's move first, then captures
+    // of the most valuable chessman by the least valuable one
+    fn order_moves(&self, moves: &mut [Move], ply: usize, tt_move: Option<Move>) {
+        let pv_move = self.pv.get(ply).copied();
+        moves.sort_by_key(|m| {
+            if Some(*m) == pv_move {
+                return i32::MIN;
+            }
+            if Some(*m) == tt_move {
+                return i32::MIN + 1;
+            }
+            match self.layout.get(m.to) {
+                Some(c) => {
+                    let attacker = self.layout.get(m.from).unwrap();
+                    self.config.material(&attacker) - 10 * self.config.material(&c)
+                }
+                None => 0,
+            }
+        });
+    }
+
+    fn out_of_budget(&self) -> bool {
+        self.limits.nodes.is_some_and(|n| self.nodes >= n)
+            || self.limits.time.is_some_and(|t| self.start.elapsed() >= t)
+    }
+}
@@ -0,0 +1,169 @@
+use core::fmt;
+
+use crate::chessman::Color;
+
+/// A column of the board, `a` to `i` from red's left.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct File(u8);
+
+/// A row of the board, 0 on red's back rank to 9 on black's, as in ICCS.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Rank(u8);
+
+/// One of the 90 points of the board.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Square(u8);
+
+impl File {
+    pub const COUNT: usize = 9;
+
+    pub fn new(index: usize) -> Option<File> {
+        (index < Self::COUNT).then_some(File(index as u8))
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn all() -> impl DoubleEndedIterator<Item = File> {
+        (0..Self::COUNT as u8).map(File)
+    }
+
+    pub fn from_char(c: char) -> Option<File> {
+        ('a'..='i').contains(&c).then(|| File(c as u8 - b'a'))
+    }
+
+    pub fn to_char(self) -> char {
+        (b'a' + self.0) as char
+    }
+
+    /// Traditional file number, 1 to 9 counted from the right of `color`.
+    pub fn number(self, color: Color) -> usize {
+        match color {
+            Color::Red => Self::COUNT - self.index(),
+            Color::Black => self.index() + 1,
+        }
+    }
+
+    /// The file with the traditional number `n` for `color`, see `number`.
+    pub fn from_number(n: usize, color: Color) -> Option<File> {
+        if !(1..=Self::COUNT).contains(&n) {
+            return None;
+        }
+        match color {
+            Color::Red => File::new(Self::COUNT - n),
+            Color::Black => File::new(n - 1),
+        }
+    }
+
+    /// Columns to the left of this file as `color` sees the board.
+    pub fn relative(self, color: Color) -> usize {
+        match color {
+            Color::Red => self.index(),
+            Color::Black => Self::COUNT - 1 - self.index(),
+        }
+    }
+}
+
+impl Rank {
+    pub const COUNT: usize = 10;
+
+    pub fn new(index: usize) -> Option<Rank> {
+        (index < Self::COUNT).then_some(Rank(index as u8))
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn all() -> impl DoubleEndedIterator<Item = Rank> {
+        (0..Self::COUNT as u8).map(Rank)
+    }
+
+    pub fn from_char(c: char) -> Option<Rank> {
+        c.to_digit(10).and_then(|n| Rank::new(n as usize))
+    }
+
+    pub fn to_char(self) -> char {
+        (b'0' + self.0) as char
+    }
+
+    /// Rows in front of the back rank of `color`.
+    pub fn relative(self, color: Color) -> usize {
+        match color {
+            Color::Red => self.index(),
+            Color::Black => Self::COUNT - 1 - self.index(),
+        }
+    }
+}
+
+impl Square {
+    pub const COUNT: usize = 90;
+
+    pub fn new(file: File, rank: Rank) -> Square {
+        Square(((Rank::COUNT - 1 - rank.index()) * File::COUNT + file.index()) as u8)
+    }
+
+    /// Squares are numbered from black's back rank down, the order a FEN
+    /// lists them in.
+    pub fn from_index(index: usize) -> Option<Square> {
+        (index < Self::COUNT).then_some(Square(index as u8))
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn all() -> impl DoubleEndedIterator<Item = Square> {
+        (0..Self::COUNT as u8).map(Square)
+    }
+
+    pub fn file(self) -> File {
+        File(self.0 % File::COUNT as u8)
+    }
+
+    pub fn rank(self) -> Rank {
+        Rank(Rank::COUNT as u8 - 1 - self.0 / File::COUNT as u8)
+    }
+
+    /// Parse ICCS coordinates such as `h2`.
+    pub fn from_iccs(s: &str) -> Option<Square> {
+        let mut chars = s.chars().map(|c| c.to_ascii_lowercase());
+        let file = File::from_char(chars.next()?)?;
+        let rank = Rank::from_char(chars.next()?)?;
+        if chars.next().is_some() {
+            return None;
+        }
+        Some(Square::new(file, rank))
+    }
+
+    /// The square `files` to the right and `ranks` up the board as red
+    /// sees it, if it is on the board.
+    pub fn offset(self, files: isize, ranks: isize) -> Option<Square> {
+        let file = File::new(self.file().index().checked_add_signed(files)?)?;
+        let rank = Rank::new(self.rank().index().checked_add_signed(ranks)?)?;
+        Some(Square::new(file, rank))
+    }
+
+    /// Whether this square is on the own half of the board for `color`.
+    pub fn is_own_half(self, color: Color) -> bool {
+        self.rank().relative(color) < Rank::COUNT / 2
+    }
+
+    /// Whether this square is in the palace of `color`.
+    pub fn is_in_palace(self, color: Color) -> bool {
+        (3..=5).contains(&self.file().index()) && self.rank().relative(color) <= 2
+    }
+}
+
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.file().to_char(), self.rank().to_char())
+    }
+}
+
+impl fmt::Debug for Square {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self}")
+    }
+}
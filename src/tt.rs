@@ -0,0 +1,110 @@
+use crate::{input::Move, search::MATE_BOUND};
+
+pub const DEFAULT_SIZE_MB: usize = 16;
+/// The largest table the engine options allow.
+pub const MAX_SIZE_MB: usize = 1024;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Bound {
+    Exact,
+    /// The score is at least this, the search failed high.
+    Lower,
+    /// The score is at most this, the search failed low.
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Entry {
+    pub key: u64,
+    pub depth: u8,
+    pub bound: Bound,
+    score: i32,
+    pub best: Option<Move>,
+}
+
+impl Entry {
+    /// The stored score, with mates counted from the node at `ply` again.
+    pub fn score(&self, ply: usize) -> i32 {
+        if self.score > MATE_BOUND {
+            self.score - ply as i32
+        } else if self.score < -MATE_BOUND {
+            self.score + ply as i32
+        } else {
+            self.score
+        }
+    }
+}
+
+/// A fixed-size table of search results keyed by `Layout::hash`.
+pub struct TranspositionTable {
+    entries: Vec<Option<Entry>>,
+}
+
+impl TranspositionTable {
+    /// A table of `size_mb` megabytes, clamped to `1..=MAX_SIZE_MB`.
+    pub fn new(size_mb: usize) -> Self {
+        Self {
+            entries: vec![None; Self::entry_count(size_mb)],
+        }
+    }
+
+    pub(crate) fn entry_count(size_mb: usize) -> usize {
+        (size_mb.clamp(1, MAX_SIZE_MB) << 20) / std::mem::size_of::<Option<Entry>>()
+    }
+
+    pub fn resize(&mut self, size_mb: usize) {
+        *self = Self::new(size_mb);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.fill(None);
+    }
+
+    pub fn probe(&self, key: u64) -> Option<Entry> {
+        self.entries[self.index(key)].filter(|e| e.key == key)
+    }
+
+    /// Store a result found at `ply`, mate scores are kept relative to the
+    /// node so they stay right wherever the position is reached again.
+    pub fn store(
+        &mut self,
+        key: u64,
+        depth: usize,
+        ply: usize,
+        bound: Bound,
+        score: i32,
+        best: Option<Move>,
+    ) {
+        let score = if score > MATE_BOUND {
+            score + ply as i32
+        } else if score < -MATE_BOUND {
+            score - ply as i32
+        } else {
+            score
+        };
+        let index = self.index(key);
+        // keep a deeper result of the same position
+        if let Some(e) = self.entries[index] {
+            if e.key == key && e.depth as usize > depth && bound != Bound::Exact {
+                return;
+            }
+        }
+        self.entries[index] = Some(Entry {
+            key,
+            depth: depth.min(u8::MAX as usize) as u8,
+            bound,
+            score,
+            best,
+        });
+    }
+
+    fn index(&self, key: u64) -> usize {
+        (key % self.entries.len() as u64) as usize
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::new(DEFAULT_SIZE_MB)
+    }
+}
@@ -0,0 +1,132 @@
+use std::io::{BufRead, Write};
+
+use crate::{
+    constants::START_FEN,
+    input::Move,
+    search::{self, Limits, SearchResult},
+    tt::{self, TranspositionTable},
+    Game,
+};
+
+/// Serve the UCCI protocol until `quit` or the end of `input`.
+pub fn run<R: BufRead, W: Write>(game: &mut Game, input: R, output: &mut W) {
+    let mut tt = TranspositionTable::default();
+    for line in input.lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("ucci") => {
+                writeln!(output, "id name chess").unwrap();
+                writeln!(output, "id author An-n-ya").unwrap();
+                writeln!(
+                    output,
+                    "option hashsize type spin min 1 max {} default {}",
+                    tt::MAX_SIZE_MB,
+                    tt::DEFAULT_SIZE_MB
+                )
+                .unwrap();
+                writeln!(output, "ucciok").unwrap();
+            }
+            Some("isready") => writeln!(output, "readyok").unwrap(),
+            Some("setoption") => match (words.next(), words.next().map(str::parse)) {
+                (Some("hashsize"), Some(Ok(size))) => tt.resize(size),
+                (Some("newgame"), _) => tt.clear(),
+                (name, _) => writeln!(
+                    output,
+                    "info string unsupported option {}",
+                    name.unwrap_or("")
+                )
+                .unwrap(),
+            },
+            Some("position") => {
+                let args: Vec<&str> = words.collect();
+                if let Err(e) = set_position(game, &args) {
+                    writeln!(output, "info string {}", e).unwrap();
+                }
+            }
+            Some("go") => {
+                let args: Vec<&str> = words.collect();
+                let result = search::search_with(
+                    &game.layout,
+                    game.turn,
+                    &parse_go(&args),
+                    &game.eval_config,
+                    &mut tt,
+                    |r| {
+                        writeln!(
+                            output,
+                            "info depth {} score {} pv {}",
+                            r.depth,
+                            r.score,
+                            pv_string(r)
+                        )
+                        .unwrap();
+                    },
+                );
+                match result.best {
+                    Some(m) => writeln!(output, "bestmove {}", m).unwrap(),
+                    None => writeln!(output, "nobestmove").unwrap(),
+                }
+            }
+            // the search runs to completion before we read the next command,
+            // so there is nothing left to stop
+            Some("stop") => {}
+            Some("quit") => {
+                writeln!(output, "bye").unwrap();
+                break;
+            }
+            _ => {}
+        }
+        output.flush().unwrap();
+    }
+}
+
+/// Handle the arguments of `position {fen <fen> | startpos} [moves <moves>]`.
+pub fn set_position(game: &mut Game, args: &[&str]) -> Result<(), String> {
+    let moves_at = args
+        .iter()
+        .position(|s| *s == "moves")
+        .unwrap_or(args.len());
+    let fen = match args.first() {
+        Some(&"startpos") => START_FEN.to_string(),
+        Some(&"fen") => args[1..moves_at].join(" "),
+        _ => return Err("expect fen or startpos".to_string()),
+    };
+    game.parse_fen(&fen).map_err(|e| e.to_string())?;
+    for s in args.iter().skip(moves_at + 1) {
+        match Move::from_iccs(s) {
+            Some(m) if game.play(&m).is_ok() => {}
+            _ => return Err(format!("invalid move {}", s)),
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn parse_go(args: &[&str]) -> Limits {
+    let mut limits = Limits::default();
+    let (mut time, mut increment, mut moves_to_go) = (None, 0, 30);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().and_then(|v| v.parse::<u64>().ok());
+        match *arg {
+            "depth" => limits.depth = value().map(|v| v as usize),
+            "nodes" => limits.nodes = value().map(|v| v as usize),
+            // UCCI gives the remaining time of the side to move in milliseconds
+            "time" => time = value(),
+            "increment" => increment = value().unwrap_or(0),
+            "movestogo" => moves_to_go = value().unwrap_or(30).max(1),
+            _ => {}
+        }
+    }
+    if let Some(time) = time {
+        limits.time = Some(search::clock_budget(time, increment, moves_to_go));
+    }
+    limits
+}
+
+pub fn pv_string(result: &SearchResult) -> String {
+    let pv: Vec<String> = result.pv.iter().map(|m| m.to_string()).collect();
+    pv.join(" ")
+}
@@ -0,0 +1,138 @@
+use std::io::{BufRead, Write};
+use std::time::{Duration, Instant};
+
+use crate::{
+    search::{self, Limits},
+    tt::{self, TranspositionTable},
+    ucci::{self, pv_string},
+    Color, Game,
+};
+
+/// Serve the UCI protocol with xiangqi FEN, as spoken by Pikafish-style GUIs,
+/// until `quit` or the end of `input`.
+pub fn run<R: BufRead, W: Write>(game: &mut Game, input: R, output: &mut W) {
+    let mut tt = TranspositionTable::default();
+    for line in input.lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("uci") => {
+                writeln!(output, "id name chess").unwrap();
+                writeln!(output, "id author An-n-ya").unwrap();
+                writeln!(
+                    output,
+                    "option name Hash type spin default {} min 1 max {}",
+                    tt::DEFAULT_SIZE_MB,
+                    tt::MAX_SIZE_MB
+                )
+                .unwrap();
+                writeln!(output, "option name Clear Hash type button").unwrap();
+                writeln!(output, "uciok").unwrap();
+            }
+            Some("isready") => writeln!(output, "readyok").unwrap(),
+            Some("setoption") => {
+                let args: Vec<&str> = words.collect();
+                match parse_setoption(&args) {
+                    (name, Some(value)) if name == "Hash" => match value.parse() {
+                        Ok(size) => tt.resize(size),
+                        Err(_) => writeln!(output, "info string invalid Hash {}", value).unwrap(),
+                    },
+                    (name, _) if name == "Clear Hash" => tt.clear(),
+                    (name, _) => {
+                        writeln!(output, "info string unsupported option {}", name).unwrap()
+                    }
+                }
+            }
+            Some("ucinewgame") => {
+                ucci::set_position(game, &["startpos"]).unwrap();
+                tt.clear();
+            }
+            Some("position") => {
+                let args: Vec<&str> = words.collect();
+                if let Err(e) = ucci::set_position(game, &args) {
+                    writeln!(output, "info string {}", e).unwrap();
+                }
+            }
+            Some("go") => {
+                let args: Vec<&str> = words.collect();
+                if let ["perft", depth] = args[..] {
+                    let depth = depth.parse().unwrap_or(1);
+                    let divide = game.layout.divide(game.turn, depth);
+                    for (m, count) in &divide {
+                        writeln!(output, "{}: {}", m, count).unwrap();
+                    }
+                    let total: usize = divide.iter().map(|(_, n)| n).sum();
+                    writeln!(output, "\nNodes searched: {}", total).unwrap();
+                    output.flush().unwrap();
+                    continue;
+                }
+                let limits = parse_go(&args, game.turn);
+                let start = Instant::now();
+                let result = search::search_with(
+                    &game.layout,
+                    game.turn,
+                    &limits,
+                    &game.eval_config,
+                    &mut tt,
+                    |r| {
+                        let score = match r.mate_in() {
+                            // UCI counts mates in moves rather than plies
+                            Some(plies) => format!("mate {}", (plies + plies.signum()) / 2),
+                            None => format!("cp {}", r.score),
+                        };
+                        let time = start.elapsed().as_millis();
+                        let pv = pv_string(r);
+                        writeln!(
+                            output,
+                            "info depth {} score {score} nodes {} time {time} pv {pv}",
+                            r.depth, r.nodes
+                        )
+                        .unwrap();
+                    },
+                );
+                match result.best {
+                    Some(m) => writeln!(output, "bestmove {}", m).unwrap(),
+                    None => writeln!(output, "bestmove (none)").unwrap(),
+                }
+            }
+            Some("stop") => {}
+            Some("quit") => break,
+            _ => {}
+        }
+        output.flush().unwrap();
+    }
+}
+
+/// Split `name <name> [value <value>]`, both of which may contain spaces.
+fn parse_setoption(args: &[&str]) -> (String, Option<String>) {
+    let args = args.strip_prefix(&["name"]).unwrap_or(args);
+    let value_at = args.iter().position(|s| *s == "value");
+    let name = args[..value_at.unwrap_or(args.len())].join(" ");
+    let value = value_at.map(|i| args[i + 1..].join(" "));
+    (name, value)
+}
+
+pub(crate) fn parse_go(args: &[&str], turn: Color) -> Limits {
+    let mut limits = Limits::default();
+    let (mut time, mut increment, mut moves_to_go) = (None, 0, 30);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().and_then(|v| v.parse::<u64>().ok());
+        match (*arg, turn) {
+            ("depth", _) => limits.depth = value().map(|v| v as usize),
+            ("nodes", _) => limits.nodes = value().map(|v| v as usize),
+            ("movetime", _) => limits.time = value().map(Duration::from_millis),
+            ("wtime", Color::Red) | ("btime", Color::Black) => time = value(),
+            ("winc", Color::Red) | ("binc", Color::Black) => increment = value().unwrap_or(0),
+            ("movestogo", _) => moves_to_go = value().unwrap_or(30).max(1),
+            _ => {}
+        }
+    }
+    // a fixed movetime wins over the clock
+    if let (None, Some(time)) = (limits.time, time) {
+        limits.time = Some(search::clock_budget(time, increment, moves_to_go));
+    }
+    limits
+}
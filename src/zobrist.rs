@@ -0,0 +1,33 @@
+use crate::{chessman::Chessman, square::Square};
+
+// 14 chessmen on 90 squares, filled at compile time from a fixed seed so
+// keys are stable across runs and can be stored in books and databases
+const KEYS: [[u64; 90]; Chessman::COUNT] = {
+    let mut keys = [[0; 90]; Chessman::COUNT];
+    let mut state = 0x9E37_79B9_7F4A_7C15u64;
+    let mut i = 0;
+    while i < Chessman::COUNT {
+        let mut j = 0;
+        while j < 90 {
+            state = splitmix64(state);
+            keys[i][j] = state;
+            j += 1;
+        }
+        i += 1;
+    }
+    keys
+};
+
+/// Xored into the key when black is to move.
+pub const SIDE_KEY: u64 = splitmix64(0x1234_5678_9ABC_DEF0);
+
+const fn splitmix64(state: u64) -> u64 {
+    let mut z = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+pub fn piece_key(chessman: &Chessman, square: Square) -> u64 {
+    KEYS[chessman.index()][square.index()]
+}